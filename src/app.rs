@@ -1,15 +1,122 @@
-use std::process::Command;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::process::Child;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use eframe::egui::{self, Color32, FontId, RichText, Rounding, Stroke, Vec2};
 
-use crate::profile::Profile;
-use crate::storage;
+use crate::launch::Launcher;
+use crate::profile::{self, Profile};
+use crate::storage::{self, Backup, CopyProgress, StorageError};
+use crate::watcher::ConfigWatcher;
+
+/// What the window hands back to `main` once it closes
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// The profile that was chosen, `None` when the window was closed without
+    /// choosing one
+    pub profile: Option<Profile>,
+    /// The program, when it was started from the window
+    pub child: Option<Child>,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 enum AppState {
     ProfileList,
     NewProfile,
     EditProfile(usize),
+    /// The profiles file was corrupt and moved aside to the given path
+    Recovery(String),
+    Backups,
+    /// Starting the program failed with the given error
+    LaunchFailed(String),
+    /// Asking what to do with the data of the profile being deleted
+    ConfirmDelete(usize),
+    /// Copying the data of a duplicated profile (see `Duplication`)
+    Duplicating,
+}
+
+/// Why a change to the profile list was not saved
+enum ChangeError {
+    /// Another manager instance saved a profile with this name first
+    NameTaken(String),
+    Storage(StorageError),
+}
+
+impl From<StorageError> for ChangeError {
+    fn from(err: StorageError) -> Self {
+        ChangeError::Storage(err)
+    }
+}
+
+/// A duplicated profile whose data directory is being copied in the background
+struct Duplication {
+    /// The copy, added to the list once its data is in place
+    profile: Profile,
+    source_name: String,
+    cancel: Arc<AtomicBool>,
+    progress: Arc<Mutex<CopyProgress>>,
+    worker: Option<JoinHandle<storage::Result<PathBuf>>>,
+}
+
+impl Duplication {
+    fn start(ctx: egui::Context, source: &Profile, copy: Profile) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let progress = Arc::new(Mutex::new(CopyProgress::default()));
+
+        let from = source.id.clone();
+        let to = copy.id.clone();
+        let worker_cancel = Arc::clone(&cancel);
+        let worker_progress = Arc::clone(&progress);
+        let worker = thread::spawn(move || {
+            storage::copy_profile_dir(&from, &to, &worker_cancel, |copied| {
+                if let Ok(mut progress) = worker_progress.lock() {
+                    *progress = copied;
+                }
+                ctx.request_repaint();
+            })
+        });
+
+        Self {
+            profile: copy,
+            source_name: source.name.clone(),
+            cancel,
+            progress,
+            worker: Some(worker),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.worker.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    fn progress(&self) -> CopyProgress {
+        self.progress.lock().map(|p| *p).unwrap_or_default()
+    }
+
+    /// Wait for the copy to end and return where the data was copied
+    fn join(&mut self) -> storage::Result<PathBuf> {
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            None => Err(StorageError::Cancelled),
+        }
+    }
+}
+
+impl Drop for Duplication {
+    /// Closing the window mid-copy cancels it, which removes the partial copy
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            self.cancel.store(true, Ordering::Relaxed);
+            let _ = worker.join();
+        }
+    }
 }
 
 pub struct ProfileApp {
@@ -20,53 +127,255 @@ pub struct ProfileApp {
     profile_name_input: String,
     profile_selected: bool,
     focus_input: bool,
-    env_var: Option<String>,
-    program: Option<String>,
+    launcher: Launcher,
+    outcome: Rc<RefCell<Outcome>>,
+    /// Hand the selected profile to `main` and close, when nothing is launched
+    close_on_select: bool,
+    /// When the preselected profile is chosen automatically (`--timeout`)
+    auto_select_at: Option<Instant>,
+    duplication: Option<Duplication>,
     should_exit: bool,
+    error: Option<String>,
+    watcher: Option<ConfigWatcher>,
+    backups: Vec<Backup>,
 }
 
 impl ProfileApp {
-    pub fn new(app_title: String, env_var: Option<String>, program: Option<String>) -> Self {
-        let mut profiles = storage::load_profiles();
-        profiles.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        let selected_name = storage::load_selected_profile();
+    pub fn new(
+        ctx: egui::Context,
+        app_title: String,
+        launcher: Launcher,
+        outcome: Rc<RefCell<Outcome>>,
+        close_on_select: bool,
+    ) -> Self {
+        let watcher = ConfigWatcher::new(move || ctx.request_repaint())
+            .inspect_err(|err| eprintln!("Live reload disabled: {}", err))
+            .ok();
+
+        let mut app = Self {
+            app_title,
+            profiles: Vec::new(),
+            selected_index: None,
+            state: AppState::ProfileList,
+            profile_name_input: String::new(),
+            profile_selected: false,
+            focus_input: false,
+            launcher,
+            outcome,
+            close_on_select,
+            auto_select_at: None,
+            duplication: None,
+            should_exit: false,
+            error: None,
+            watcher,
+            backups: Vec::new(),
+        };
+
+        match storage::load_profiles() {
+            Ok(profiles) => app.set_profiles(profiles),
+            Err(StorageError::Corrupt { quarantined, .. }) => {
+                app.state = AppState::Recovery(quarantined.display().to_string());
+            }
+            Err(err) => {
+                app.error = Some(format!("Failed to load profiles: {}", err));
+                app.set_profiles(Vec::new());
+            }
+        }
+
+        app
+    }
+
+    /// Open the new profile form for `name`, a profile that was asked for but
+    /// does not exist
+    pub fn offer_to_create(&mut self, name: String) {
+        if matches!(self.state, AppState::Recovery(_)) {
+            return;
+        }
+        self.error = Some(format!(
+            "Profile '{}' does not exist. Create it or choose another one.",
+            name
+        ));
+        self.profile_name_input = name;
+        self.focus_input = true;
+        self.state = AppState::NewProfile;
+    }
+
+    /// Choose the last selected profile automatically after `timeout`, unless
+    /// the user presses a key or clicks first. Nothing is chosen when no
+    /// stored selection matches an existing profile.
+    pub fn start_countdown(&mut self, timeout: Duration) {
+        if self.state != AppState::ProfileList {
+            return;
+        }
+        let remembered = storage::load_selected_profile()
+            .ok()
+            .flatten()
+            .and_then(|id| self.profiles.iter().position(|p| p.id == id));
+        if let Some(idx) = remembered {
+            self.selected_index = Some(idx);
+            self.auto_select_at = Some(Instant::now() + timeout);
+        }
+    }
+
+    /// Whole seconds left before the preselected profile is chosen
+    fn countdown_secs(&self) -> Option<u64> {
+        self.auto_select_at.map(|at| {
+            let left = at.saturating_duration_since(Instant::now());
+            left.as_secs() + u64::from(left.subsec_nanos() > 0)
+        })
+    }
 
-        let selected_index = if profiles.is_empty() {
+    fn update_countdown(&mut self, ctx: &egui::Context) {
+        let Some(at) = self.auto_select_at else {
+            return;
+        };
+
+        let interrupted = ctx.input(|i| {
+            i.events.iter().any(|event| {
+                matches!(
+                    event,
+                    egui::Event::Key { pressed: true, .. }
+                        | egui::Event::PointerButton { pressed: true, .. }
+                        | egui::Event::Text(_)
+                )
+            })
+        });
+        if interrupted || self.state != AppState::ProfileList || self.selected_index.is_none() {
+            self.auto_select_at = None;
+        } else if Instant::now() >= at {
+            self.auto_select_at = None;
+            self.choose_selected_profile();
+            ctx.request_repaint();
+        } else {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    /// Replace the profile list and restore the stored selection
+    fn set_profiles(&mut self, mut profiles: Vec<Profile>) {
+        profile::sort_profiles(&mut profiles);
+        let selected_id = storage::load_selected_profile().unwrap_or_else(|err| {
+            self.error
+                .get_or_insert(format!("Failed to load the selected profile: {}", err));
+            None
+        });
+
+        self.selected_index = if profiles.is_empty() {
             None
         } else {
-            selected_name
-                .and_then(|name| profiles.iter().position(|p| p.name == name))
+            selected_id
+                .and_then(|id| profiles.iter().position(|p| p.id == id))
                 .or(Some(profiles.len() - 1))
         };
 
-        let state = if profiles.is_empty() {
+        self.state = if profiles.is_empty() {
             AppState::NewProfile
         } else {
             AppState::ProfileList
         };
 
-        let focus_input = profiles.is_empty();
+        self.focus_input = profiles.is_empty();
+        self.profiles = profiles;
+    }
 
-        Self {
-            app_title,
-            profiles,
-            selected_index,
-            state,
-            profile_name_input: String::new(),
-            profile_selected: false,
-            focus_input,
-            env_var,
-            program,
-            should_exit: false,
+    /// Pick up changes made on disk by other processes, keeping the current
+    /// view and selection where they still apply
+    fn reload_profiles(&mut self) {
+        if matches!(self.state, AppState::Recovery(_)) {
+            return;
+        }
+
+        // A file that does not parse is left alone and read again on the next
+        // change event, once its writer is done
+        let profiles = match storage::read_profiles() {
+            Ok(profiles) => profiles,
+            Err(StorageError::Corrupt { quarantined, .. }) => {
+                self.state = AppState::Recovery(quarantined.display().to_string());
+                return;
+            }
+            Err(err) => {
+                self.error = Some(format!("Failed to reload profiles: {}", err));
+                return;
+            }
+        };
+
+        let selected_id = self.get_selected_profile().map(|p| p.id.clone());
+        let editing_id = match self.state {
+            AppState::EditProfile(idx) | AppState::ConfirmDelete(idx) => {
+                self.profiles.get(idx).map(|p| p.id.clone())
+            }
+            _ => None,
+        };
+        let previous_state = self.state.clone();
+
+        self.set_profiles(profiles);
+
+        if let Some(idx) = selected_id.and_then(|id| self.profiles.iter().position(|p| p.id == id))
+        {
+            self.selected_index = Some(idx);
+        }
+
+        match previous_state {
+            AppState::NewProfile => {
+                self.state = AppState::NewProfile;
+            }
+            state @ (AppState::LaunchFailed(_) | AppState::Duplicating) => self.state = state,
+            AppState::EditProfile(_) => {
+                match editing_id.and_then(|id| self.profiles.iter().position(|p| p.id == id)) {
+                    Some(idx) => self.state = AppState::EditProfile(idx),
+                    None => self.profile_name_input.clear(),
+                }
+            }
+            AppState::ConfirmDelete(_) => {
+                if let Some(idx) =
+                    editing_id.and_then(|id| self.profiles.iter().position(|p| p.id == id))
+                {
+                    self.state = AppState::ConfirmDelete(idx);
+                }
+            }
+            _ => {}
         }
     }
 
-    fn launch_program_and_exit(&mut self, profile_name: &str) {
-        if let (Some(env_var), Some(program)) = (&self.env_var, &self.program) {
-            let _ = Command::new(program)
-                .env(env_var, profile_name)
-                .spawn();
-            self.should_exit = true;
+    /// Start the program for `profile` and close the window, or show why it
+    /// could not be started
+    fn launch_program_and_exit(&mut self, profile: &Profile) {
+        // With --exec, main replaces the process with the program instead
+        let child = if self.launcher.replaces_process() {
+            None
+        } else {
+            match self.launcher.spawn(profile) {
+                Ok(child) => Some(child),
+                Err(err) => {
+                    self.state = AppState::LaunchFailed(err.to_string());
+                    return;
+                }
+            }
+        };
+
+        let mut outcome = self.outcome.borrow_mut();
+        outcome.profile = Some(profile.clone());
+        outcome.child = child;
+        self.should_exit = true;
+    }
+
+    /// Remember the selected profile, then launch the program or hand the
+    /// profile to `main`
+    fn choose_selected_profile(&mut self) {
+        let Some(profile) = self.get_selected_profile().cloned() else {
+            return;
+        };
+        if let Err(err) = storage::save_selected_profile(&profile.id) {
+            self.error = Some(format!("Failed to save the selected profile: {}", err));
+        } else if self.launcher.is_configured() {
+            self.launch_program_and_exit(&profile);
+        } else {
+            self.outcome.borrow_mut().profile = Some(profile);
+            if self.close_on_select {
+                self.should_exit = true;
+            } else {
+                self.profile_selected = true;
+            }
         }
     }
 
@@ -74,8 +383,62 @@ impl ProfileApp {
         self.selected_index.and_then(|i| self.profiles.get(i))
     }
 
-    fn save_profiles(&self) {
-        storage::save_profiles(&self.profiles);
+    /// Apply a change on top of the latest on-disk profiles and adopt the
+    /// merged list, so edits from other manager instances are not lost.
+    /// Returns whether the change was saved.
+    fn update_profiles(
+        &mut self,
+        change: impl FnOnce(&mut Vec<Profile>) -> Result<(), ChangeError>,
+    ) -> bool {
+        match storage::update_profiles(change) {
+            Ok(mut profiles) => {
+                profile::sort_profiles(&mut profiles);
+                self.profiles = profiles;
+                true
+            }
+            Err(ChangeError::NameTaken(name)) => {
+                self.error = Some(format!("A profile named \"{}\" already exists", name));
+                false
+            }
+            Err(ChangeError::Storage(err)) => {
+                self.error = Some(format!("Failed to save profiles: {}", err));
+                false
+            }
+        }
+    }
+
+    fn render_error_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(error) = &self.error else {
+            return;
+        };
+
+        let mut dismissed = false;
+        egui::Frame::none()
+            .fill(Color32::from_rgb(90, 35, 35))
+            .rounding(Rounding::same(6.0))
+            .inner_margin(Vec2::new(10.0, 6.0))
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Label::new(
+                            RichText::new(error)
+                                .font(FontId::proportional(13.0))
+                                .color(Color32::from_rgb(255, 200, 200)),
+                        )
+                        .wrap(),
+                    );
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✕").clicked() {
+                            dismissed = true;
+                        }
+                    });
+                });
+            });
+
+        if dismissed {
+            self.error = None;
+        }
     }
 
     fn render_profile_list(&mut self, ui: &mut egui::Ui) {
@@ -116,6 +479,15 @@ impl ProfileApp {
                         .max_height(180.0)
                         .show(ui, |ui| {
                             ui.set_width(ui.available_width());
+                            let action = if self.launcher.is_configured() {
+                                "Launching"
+                            } else {
+                                "Selecting"
+                            };
+                            let countdown = self
+                                .countdown_secs()
+                                .map(|secs| format!("{} in {}s", action, secs));
+                            let countdown = countdown.as_deref();
                             for (i, profile) in self.profiles.iter().enumerate() {
                                 let is_selected = self.selected_index == Some(i);
                                 let bg_color = if is_selected {
@@ -135,11 +507,16 @@ impl ProfileApp {
                                     .inner_margin(Vec2::new(10.0, 6.0))
                                     .show(ui, |ui| {
                                         ui.set_width(ui.available_width());
-                                        ui.label(
-                                            RichText::new(&profile.name)
-                                                .font(FontId::proportional(15.0))
-                                                .color(text_color),
-                                        );
+                                        ui.horizontal(|ui| {
+                                            ui.label(
+                                                RichText::new(&profile.name)
+                                                    .font(FontId::proportional(15.0))
+                                                    .color(text_color),
+                                            );
+                                            if let Some(text) = countdown.filter(|_| is_selected) {
+                                                countdown_label(ui, text);
+                                            }
+                                        });
                                     });
                                 if row_response.response.interact(egui::Sense::click()).clicked() {
                                     self.selected_index = Some(i);
@@ -154,7 +531,7 @@ impl ProfileApp {
 
         let button_size = Vec2::new(80.0, 30.0);
         let has_selection = self.selected_index.is_some();
-        let num_buttons = if has_selection { 3 } else { 1 };
+        let num_buttons = if has_selection { 4 } else { 1 };
         let total_width = (button_size.x * num_buttons as f32) + (8.0 * (num_buttons - 1) as f32);
         let available_width = ui.available_width();
         let offset = (available_width - total_width) / 2.0;
@@ -176,19 +553,20 @@ impl ProfileApp {
                     self.state = AppState::EditProfile(idx);
                 }
 
+                if styled_button(
+                    ui,
+                    "Duplicate",
+                    Color32::from_rgb(130, 110, 170),
+                    button_size,
+                )
+                .clicked()
+                {
+                    self.duplicate_profile(ui.ctx(), idx);
+                }
+
                 if styled_button(ui, "Delete", Color32::from_rgb(180, 80, 80), button_size).clicked()
                 {
-                    self.profiles.remove(idx);
-                    self.save_profiles();
-
-                    if self.profiles.is_empty() {
-                        self.selected_index = None;
-                        self.focus_input = true;
-                        self.state = AppState::NewProfile;
-                    } else {
-                        self.selected_index =
-                            Some(idx.saturating_sub(1).min(self.profiles.len() - 1));
-                    }
+                    self.state = AppState::ConfirmDelete(idx);
                 }
             }
         });
@@ -205,18 +583,124 @@ impl ProfileApp {
                 )
                 .clicked()
                 {
-                    if let Some(profile) = self.get_selected_profile() {
-                        let profile_name = profile.name.clone();
-                        storage::save_selected_profile(&profile_name);
-                        if self.env_var.is_some() && self.program.is_some() {
-                            self.launch_program_and_exit(&profile_name);
-                        } else {
-                            self.profile_selected = true;
-                        }
+                    self.choose_selected_profile();
+                }
+            });
+        }
+
+        ui.add_space(10.0);
+
+        ui.vertical_centered(|ui| {
+            let restore = egui::Button::new(
+                RichText::new("Restore backup...")
+                    .font(FontId::proportional(13.0))
+                    .color(Color32::GRAY),
+            )
+            .frame(false);
+            if ui.add(restore).clicked() {
+                match storage::list_backups() {
+                    Ok(backups) => {
+                        self.backups = backups;
+                        self.state = AppState::Backups;
                     }
+                    Err(err) => self.error = Some(format!("Failed to list backups: {}", err)),
                 }
+            }
+        });
+    }
+
+    fn render_backups(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new("Restore Backup")
+                    .font(FontId::proportional(24.0))
+                    .color(Color32::from_rgb(100, 150, 255)),
+            );
+            ui.add_space(5.0);
+            ui.label(
+                RichText::new("Replace the profile list with a previous version")
+                    .font(FontId::proportional(14.0))
+                    .color(Color32::GRAY),
+            );
+        });
+
+        ui.add_space(20.0);
+
+        let mut restore = None;
+
+        if self.backups.is_empty() {
+            ui.vertical_centered(|ui| {
+                ui.label(
+                    RichText::new("No backups yet")
+                        .font(FontId::proportional(16.0))
+                        .color(Color32::from_rgb(255, 180, 100)),
+                );
+                ui.add_space(5.0);
+                ui.label("A backup is kept every time the profile list changes");
             });
+        } else {
+            egui::Frame::none()
+                .fill(Color32::from_rgb(35, 35, 40))
+                .rounding(Rounding::same(8.0))
+                .inner_margin(10.0)
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(180.0)
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            for (i, backup) in self.backups.iter().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "{}  ({} profiles)",
+                                            backup.created_utc(),
+                                            backup.profile_count
+                                        ))
+                                        .font(FontId::proportional(14.0))
+                                        .color(Color32::from_rgb(200, 200, 200)),
+                                    );
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            if ui.small_button("Restore").clicked() {
+                                                restore = Some(i);
+                                            }
+                                        },
+                                    );
+                                });
+                                ui.add_space(2.0);
+                            }
+                        });
+                });
         }
+
+        if let Some(i) = restore {
+            match storage::restore_backup(&self.backups[i]) {
+                Ok(profiles) => {
+                    self.backups.clear();
+                    self.set_profiles(profiles);
+                }
+                Err(err) => self.error = Some(format!("Failed to restore backup: {}", err)),
+            }
+            return;
+        }
+
+        ui.add_space(20.0);
+
+        ui.vertical_centered(|ui| {
+            if styled_button(
+                ui,
+                "Back",
+                Color32::from_rgb(100, 100, 100),
+                Vec2::new(100.0, 35.0),
+            )
+            .clicked()
+            {
+                self.backups.clear();
+                self.state = AppState::ProfileList;
+            }
+        });
     }
 
     fn render_new_profile(&mut self, ui: &mut egui::Ui) {
@@ -339,21 +823,114 @@ impl ProfileApp {
 
     fn create_profile(&mut self) {
         let name = self.profile_name_input.trim().to_string();
-        let profile = Profile::new(name.clone());
-        self.profiles.push(profile);
-        self.profiles.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        self.save_profiles();
-        self.selected_index = self.profiles.iter().position(|p| p.name == name);
+        let profile = Profile::new(name);
+        let id = profile.id.clone();
+        let saved = self.update_profiles(|profiles| {
+            // The list on disk may have changed since the name was checked
+            if profiles.iter().any(|p| p.name == profile.name) {
+                return Err(ChangeError::NameTaken(profile.name));
+            }
+            profiles.push(profile);
+            Ok(())
+        });
+        if !saved {
+            return;
+        }
+        self.selected_index = self.profiles.iter().position(|p| p.id == id);
+        if self.selected_index.is_some() {
+            if let Err(err) = storage::ensure_profile_dir(&id) {
+                self.error = Some(format!("Failed to create the profile folder: {}", err));
+            }
+        }
         self.profile_name_input.clear();
         self.state = AppState::ProfileList;
     }
 
+    /// Delete the profile at `idx`, archiving its data directory or deleting it
+    fn delete_profile(&mut self, idx: usize, archive_data: bool) {
+        let id = self.profiles[idx].id.clone();
+        self.update_profiles(|profiles| {
+            profiles.retain(|p| p.id != id);
+            Ok(())
+        });
+
+        if !self.profiles.iter().any(|p| p.id == id) {
+            let result = if archive_data {
+                storage::archive_profile_dir(&id).map(|_| ())
+            } else {
+                storage::remove_profile_dir(&id)
+            };
+            if let Err(err) = result {
+                self.error = Some(format!("Failed to remove the profile folder: {}", err));
+            }
+        }
+
+        if self.profiles.is_empty() {
+            self.selected_index = None;
+            self.focus_input = true;
+            self.state = AppState::NewProfile;
+        } else {
+            self.selected_index = Some(idx.saturating_sub(1).min(self.profiles.len() - 1));
+            self.state = AppState::ProfileList;
+        }
+    }
+
+    /// Start copying the profile at `idx`, data included, under a new name
+    fn duplicate_profile(&mut self, ctx: &egui::Context, idx: usize) {
+        let source = &self.profiles[idx];
+        let copy = source.duplicate(profile::copy_name(&self.profiles, &source.name));
+        self.duplication = Some(Duplication::start(ctx.clone(), source, copy));
+        self.state = AppState::Duplicating;
+    }
+
+    /// Add the copy once its data is in place, or report why it was not made
+    fn finish_duplication(&mut self) {
+        let Some(mut duplication) = self.duplication.take() else {
+            return;
+        };
+
+        match duplication.join() {
+            Ok(_) => {
+                let mut copy = duplication.profile.clone();
+                let id = copy.id.clone();
+                let source_name = duplication.source_name.clone();
+                self.update_profiles(|profiles| {
+                    // Another instance may have taken the name in the meantime
+                    if profiles.iter().any(|p| p.name == copy.name) {
+                        copy.name = profile::copy_name(profiles, &source_name);
+                    }
+                    profiles.push(copy);
+                    Ok(())
+                });
+                match self.profiles.iter().position(|p| p.id == id) {
+                    Some(idx) => self.selected_index = Some(idx),
+                    None => {
+                        let _ = storage::remove_profile_dir(&id);
+                    }
+                }
+            }
+            Err(StorageError::Cancelled) => {}
+            Err(err) => self.error = Some(format!("Failed to copy the profile data: {}", err)),
+        }
+        self.state = AppState::ProfileList;
+    }
+
     fn save_edited_profile(&mut self, edit_index: usize) {
+        let id = self.profiles[edit_index].id.clone();
         let name = self.profile_name_input.trim().to_string();
-        self.profiles[edit_index].name = name.clone();
-        self.profiles.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        self.save_profiles();
-        self.selected_index = self.profiles.iter().position(|p| p.name == name);
+        let saved = self.update_profiles(|profiles| {
+            if profiles.iter().any(|p| p.name == name && p.id != id) {
+                return Err(ChangeError::NameTaken(name));
+            }
+            if let Some(profile) = profiles.iter_mut().find(|p| p.id == id) {
+                profile.name = name;
+            }
+            Ok(())
+        });
+        if !saved {
+            return;
+        }
+        self.selected_index = self.profiles.iter().position(|p| p.id == id);
         self.profile_name_input.clear();
         self.state = AppState::ProfileList;
     }
@@ -473,6 +1050,313 @@ impl ProfileApp {
             }
         });
     }
+
+    fn render_recovery(&mut self, ui: &mut egui::Ui, quarantined: &str) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new("Profile Data Damaged")
+                    .font(FontId::proportional(24.0))
+                    .color(Color32::from_rgb(255, 180, 100)),
+            );
+            ui.add_space(5.0);
+            ui.label(
+                RichText::new("The profiles file could not be read and was moved to:")
+                    .font(FontId::proportional(14.0))
+                    .color(Color32::GRAY),
+            );
+        });
+
+        ui.add_space(10.0);
+
+        egui::Frame::none()
+            .fill(Color32::from_rgb(35, 35, 40))
+            .rounding(Rounding::same(8.0))
+            .inner_margin(10.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.add(
+                    egui::Label::new(
+                        RichText::new(quarantined)
+                            .font(FontId::monospace(12.0))
+                            .color(Color32::from_rgb(200, 200, 200)),
+                    )
+                    .wrap(),
+                );
+            });
+
+        ui.add_space(20.0);
+
+        let has_backup = storage::has_last_good();
+        let button_size = Vec2::new(120.0, 35.0);
+        let num_buttons = if has_backup { 2 } else { 1 };
+        let total_width = (button_size.x * num_buttons as f32) + (10.0 * (num_buttons - 1) as f32);
+        let available_width = ui.available_width();
+        let offset = (available_width - total_width) / 2.0;
+
+        ui.horizontal(|ui| {
+            ui.add_space(offset);
+
+            if has_backup {
+                if styled_button(
+                    ui,
+                    "Restore Backup",
+                    Color32::from_rgb(80, 130, 180),
+                    button_size,
+                )
+                .clicked()
+                {
+                    match storage::restore_last_good() {
+                        Ok(profiles) => self.set_profiles(profiles),
+                        Err(err) => {
+                            self.error = Some(format!("Failed to restore backup: {}", err));
+                        }
+                    }
+                }
+
+                ui.add_space(10.0);
+            }
+
+            if styled_button(
+                ui,
+                "Start Fresh",
+                Color32::from_rgb(100, 100, 100),
+                button_size,
+            )
+            .clicked()
+            {
+                match storage::start_fresh() {
+                    Ok(()) => self.set_profiles(Vec::new()),
+                    Err(err) => self.error = Some(format!("Failed to start fresh: {}", err)),
+                }
+            }
+        });
+    }
+
+    fn render_confirm_delete(&mut self, ui: &mut egui::Ui, idx: usize) {
+        let Some(profile) = self.profiles.get(idx) else {
+            self.state = AppState::ProfileList;
+            return;
+        };
+        let profile_dir = storage::get_profile_dir(&profile.id).filter(|dir| dir.exists());
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new("Delete Profile")
+                    .font(FontId::proportional(24.0))
+                    .color(Color32::from_rgb(255, 120, 120)),
+            );
+            ui.add_space(5.0);
+            ui.label(
+                RichText::new(format!("Delete \"{}\"?", profile.name))
+                    .font(FontId::proportional(14.0))
+                    .color(Color32::GRAY),
+            );
+        });
+
+        ui.add_space(10.0);
+
+        if let Some(dir) = &profile_dir {
+            ui.label(
+                RichText::new("Its data folder can be archived or deleted with it:")
+                    .font(FontId::proportional(13.0))
+                    .color(Color32::GRAY),
+            );
+            ui.add_space(5.0);
+            egui::Frame::none()
+                .fill(Color32::from_rgb(35, 35, 40))
+                .rounding(Rounding::same(8.0))
+                .inner_margin(10.0)
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.add(
+                        egui::Label::new(
+                            RichText::new(dir.display().to_string())
+                                .font(FontId::monospace(12.0))
+                                .color(Color32::from_rgb(200, 200, 200)),
+                        )
+                        .wrap(),
+                    );
+                });
+        }
+
+        ui.add_space(20.0);
+
+        let button_size = Vec2::new(100.0, 35.0);
+        let num_buttons = if profile_dir.is_some() { 3 } else { 2 };
+        let total_width = (button_size.x * num_buttons as f32) + (10.0 * (num_buttons - 1) as f32);
+        let available_width = ui.available_width();
+        let offset = (available_width - total_width) / 2.0;
+
+        ui.horizontal(|ui| {
+            ui.add_space(offset);
+
+            if profile_dir.is_some() {
+                if styled_button(ui, "Archive", Color32::from_rgb(80, 130, 180), button_size)
+                    .clicked()
+                {
+                    self.delete_profile(idx, true);
+                }
+                ui.add_space(10.0);
+            }
+
+            let delete_label = if profile_dir.is_some() {
+                "Delete All"
+            } else {
+                "Delete"
+            };
+            if styled_button(
+                ui,
+                delete_label,
+                Color32::from_rgb(180, 80, 80),
+                button_size,
+            )
+            .clicked()
+            {
+                self.delete_profile(idx, false);
+            }
+
+            ui.add_space(10.0);
+
+            if styled_button(ui, "Cancel", Color32::from_rgb(100, 100, 100), button_size).clicked()
+            {
+                self.state = AppState::ProfileList;
+            }
+        });
+    }
+
+    fn render_duplicating(&mut self, ui: &mut egui::Ui) {
+        let Some(duplication) = &self.duplication else {
+            self.state = AppState::ProfileList;
+            return;
+        };
+        let progress = duplication.progress();
+        let cancelling = duplication.cancel.load(Ordering::Relaxed);
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new("Duplicate Profile")
+                    .font(FontId::proportional(24.0))
+                    .color(Color32::WHITE),
+            );
+            ui.add_space(5.0);
+            ui.label(
+                RichText::new(format!(
+                    "Copying the data of \"{}\" to \"{}\"",
+                    duplication.source_name, duplication.profile.name
+                ))
+                .font(FontId::proportional(14.0))
+                .color(Color32::GRAY),
+            );
+        });
+
+        ui.add_space(20.0);
+
+        ui.add(
+            egui::ProgressBar::new(progress.fraction())
+                .text(progress.to_string())
+                .fill(Color32::from_rgb(130, 110, 170)),
+        );
+
+        ui.add_space(20.0);
+
+        ui.vertical_centered(|ui| {
+            let label = if cancelling {
+                "Cancelling..."
+            } else {
+                "Cancel"
+            };
+            if styled_button(
+                ui,
+                label,
+                Color32::from_rgb(100, 100, 100),
+                Vec2::new(100.0, 35.0),
+            )
+            .clicked()
+            {
+                duplication.cancel.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+
+    fn render_launch_failed(&mut self, ui: &mut egui::Ui, error: &str) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new("Launch Failed")
+                    .font(FontId::proportional(24.0))
+                    .color(Color32::from_rgb(255, 120, 120)),
+            );
+            ui.add_space(5.0);
+            ui.label(
+                RichText::new("The program could not be started:")
+                    .font(FontId::proportional(14.0))
+                    .color(Color32::GRAY),
+            );
+        });
+
+        ui.add_space(10.0);
+
+        egui::Frame::none()
+            .fill(Color32::from_rgb(35, 35, 40))
+            .rounding(Rounding::same(8.0))
+            .inner_margin(10.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.add(
+                    egui::Label::new(
+                        RichText::new(error)
+                            .font(FontId::monospace(12.0))
+                            .color(Color32::from_rgb(200, 200, 200)),
+                    )
+                    .wrap(),
+                );
+            });
+
+        ui.add_space(20.0);
+
+        let retry_size = Vec2::new(90.0, 35.0);
+        let choose_size = Vec2::new(180.0, 35.0);
+        let total_width = retry_size.x + 10.0 + choose_size.x;
+        let available_width = ui.available_width();
+        let offset = (available_width - total_width) / 2.0;
+
+        ui.horizontal(|ui| {
+            ui.add_space(offset);
+
+            if styled_button(ui, "Retry", Color32::from_rgb(100, 180, 100), retry_size).clicked() {
+                if let Some(profile) = self.get_selected_profile().cloned() {
+                    self.launch_program_and_exit(&profile);
+                }
+            }
+
+            ui.add_space(10.0);
+
+            if styled_button(
+                ui,
+                "Choose another profile",
+                Color32::from_rgb(80, 130, 180),
+                choose_size,
+            )
+            .clicked()
+            {
+                self.state = AppState::ProfileList;
+            }
+        });
+    }
+}
+
+/// Right-aligned countdown shown on the preselected row
+fn countdown_label(ui: &mut egui::Ui, text: &str) {
+    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+        ui.label(
+            RichText::new(text)
+                .font(FontId::proportional(13.0))
+                .color(Color32::from_rgb(200, 220, 255)),
+        );
+    });
 }
 
 fn styled_button(ui: &mut egui::Ui, text: &str, color: Color32, size: Vec2) -> egui::Response {
@@ -498,6 +1382,20 @@ impl eframe::App for ProfileApp {
             return;
         }
 
+        if self.watcher.as_ref().is_some_and(|w| w.take_changed()) {
+            self.reload_profiles();
+        }
+
+        self.update_countdown(ctx);
+
+        if self
+            .duplication
+            .as_ref()
+            .is_some_and(Duplication::is_finished)
+        {
+            self.finish_duplication();
+        }
+
         if self.profile_selected {
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
@@ -532,10 +1430,18 @@ impl eframe::App for ProfileApp {
             return;
         }
 
-        egui::CentralPanel::default().show(ctx, |ui| match self.state.clone() {
-            AppState::ProfileList => self.render_profile_list(ui),
-            AppState::NewProfile => self.render_new_profile(ui),
-            AppState::EditProfile(idx) => self.render_edit_profile(ui, idx),
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.render_error_banner(ui);
+            match self.state.clone() {
+                AppState::ProfileList => self.render_profile_list(ui),
+                AppState::NewProfile => self.render_new_profile(ui),
+                AppState::EditProfile(idx) => self.render_edit_profile(ui, idx),
+                AppState::Recovery(quarantined) => self.render_recovery(ui, &quarantined),
+                AppState::Backups => self.render_backups(ui),
+                AppState::LaunchFailed(error) => self.render_launch_failed(ui, &error),
+                AppState::ConfirmDelete(idx) => self.render_confirm_delete(ui, idx),
+                AppState::Duplicating => self.render_duplicating(ui),
+            }
         });
     }
 }
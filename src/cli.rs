@@ -0,0 +1,324 @@
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::process::ExitCode;
+#[cfg(not(unix))]
+use std::sync::atomic::AtomicBool;
+
+use clap::Subcommand;
+
+use crate::profile::{self, Profile};
+#[cfg(unix)]
+use crate::signals;
+use crate::storage::{self, StorageError};
+
+/// Headless profile management commands (no window is opened)
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List all profile names, one per line
+    List {
+        /// Print the profile ID before each name, separated by a tab
+        #[arg(long)]
+        ids: bool,
+    },
+    /// Create a new profile
+    Create {
+        /// Name of the new profile
+        name: String,
+    },
+    /// Rename an existing profile
+    Rename {
+        /// Current profile name or ID
+        name: String,
+        /// New profile name
+        new_name: String,
+    },
+    /// Copy a profile, including its data directory, under a new name
+    Duplicate {
+        /// Name or ID of the profile to copy
+        name: String,
+        /// Name of the copy (defaults to "<name> (copy)")
+        new_name: Option<String>,
+    },
+    /// Delete a profile; its data directory is moved to archive/
+    Delete {
+        /// Name or ID of the profile to delete
+        name: String,
+        /// Delete the profile's data directory instead of archiving it
+        #[arg(long)]
+        delete_data: bool,
+    },
+    /// Mark a profile as the selected one
+    Select {
+        /// Name or ID of the profile to select
+        name: String,
+    },
+    /// Print the name of the selected profile
+    Current,
+    /// List or restore automatic backups of the profile list
+    Backups {
+        #[command(subcommand)]
+        action: BackupsCommand,
+    },
+    /// Rewrite the profiles file in another format, replacing the current one
+    Convert {
+        /// Target format
+        #[arg(long, value_enum)]
+        to: storage::Format,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupsCommand {
+    /// List backups, newest first
+    List,
+    /// Restore a backup, replacing the current profile list
+    Restore {
+        /// Backup number as shown by `backups list`
+        number: usize,
+    },
+}
+
+// Exit codes returned by the headless commands
+const EXIT_ERROR: u8 = 1;
+const EXIT_INVALID_NAME: u8 = 2;
+const EXIT_NOT_FOUND: u8 = 3;
+const EXIT_ALREADY_EXISTS: u8 = 4;
+/// Returned when the window is closed without choosing a profile, or when
+/// `duplicate` is interrupted
+pub const EXIT_CANCELLED: u8 = 5;
+
+#[derive(Debug)]
+enum CliError {
+    InvalidName,
+    NotFound(String),
+    AlreadyExists(String),
+    NoSelection,
+    NoSuchBackup(usize),
+    Storage(StorageError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::InvalidName => EXIT_INVALID_NAME,
+            CliError::NotFound(_) | CliError::NoSelection | CliError::NoSuchBackup(_) => {
+                EXIT_NOT_FOUND
+            }
+            CliError::AlreadyExists(_) => EXIT_ALREADY_EXISTS,
+            CliError::Storage(StorageError::Cancelled) => EXIT_CANCELLED,
+            CliError::Storage(_) => EXIT_ERROR,
+        }
+    }
+}
+
+impl From<StorageError> for CliError {
+    fn from(err: StorageError) -> Self {
+        CliError::Storage(err)
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::InvalidName => write!(f, "profile name cannot be empty"),
+            CliError::NotFound(name) => write!(f, "profile '{}' does not exist", name),
+            CliError::AlreadyExists(name) => write!(f, "profile '{}' already exists", name),
+            CliError::NoSelection => write!(f, "no profile is selected"),
+            CliError::NoSuchBackup(number) => write!(f, "backup {} does not exist", number),
+            CliError::Storage(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+pub fn run(command: Command) -> ExitCode {
+    let result = match command {
+        Command::List { ids } => list(ids),
+        Command::Create { name } => create(&name),
+        Command::Rename { name, new_name } => rename(&name, &new_name),
+        Command::Duplicate { name, new_name } => duplicate(&name, new_name.as_deref()),
+        Command::Delete { name, delete_data } => delete(&name, delete_data),
+        Command::Select { name } => select(&name),
+        Command::Current => current(),
+        Command::Backups {
+            action: BackupsCommand::List,
+        } => list_backups(),
+        Command::Backups {
+            action: BackupsCommand::Restore { number },
+        } => restore_backup(number),
+        Command::Convert { to } => convert(to),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, CliError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CliError::InvalidName);
+    }
+    Ok(name.to_string())
+}
+
+fn find_profile(profiles: &[Profile], key: &str) -> Result<usize, CliError> {
+    profile::find_profile(profiles, key).ok_or_else(|| CliError::NotFound(key.to_string()))
+}
+
+fn list(ids: bool) -> Result<(), CliError> {
+    let mut profiles = storage::load_profiles()?;
+    profile::sort_profiles(&mut profiles);
+    for profile in &profiles {
+        if ids {
+            println!("{}\t{}", profile.id, profile.name);
+        } else {
+            println!("{}", profile.name);
+        }
+    }
+    Ok(())
+}
+
+fn create(name: &str) -> Result<(), CliError> {
+    let name = validate_name(name)?;
+    let profile = Profile::new(name);
+    let id = profile.id.clone();
+    storage::update_profiles(|profiles| {
+        if profiles.iter().any(|p| p.name == profile.name) {
+            return Err(CliError::AlreadyExists(profile.name));
+        }
+        profiles.push(profile);
+        profile::sort_profiles(profiles);
+        Ok(())
+    })?;
+    storage::ensure_profile_dir(&id)?;
+    Ok(())
+}
+
+fn rename(name: &str, new_name: &str) -> Result<(), CliError> {
+    let new_name = validate_name(new_name)?;
+    storage::update_profiles(|profiles| {
+        let idx = find_profile(profiles, name)?;
+        if new_name != profiles[idx].name && profiles.iter().any(|p| p.name == new_name) {
+            return Err(CliError::AlreadyExists(new_name));
+        }
+        profiles[idx].name = new_name;
+        profile::sort_profiles(profiles);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Copy the data directory first and add the profile once it is complete, so
+/// an interrupted copy leaves nothing behind. Prints the new profile's name.
+fn duplicate(name: &str, new_name: Option<&str>) -> Result<(), CliError> {
+    let new_name = new_name.map(validate_name).transpose()?;
+    let profiles = storage::load_profiles()?;
+    let source = &profiles[find_profile(&profiles, name)?];
+    let new_name = match new_name {
+        Some(new_name) if profiles.iter().any(|p| p.name == new_name) => {
+            return Err(CliError::AlreadyExists(new_name));
+        }
+        Some(new_name) => new_name,
+        None => profile::copy_name(&profiles, &source.name),
+    };
+    let copy = source.duplicate(new_name);
+
+    let show_progress = io::stderr().is_terminal();
+    // Ctrl-C stops the copy cleanly instead of leaving half a data directory
+    #[cfg(unix)]
+    let cancel = signals::catch();
+    #[cfg(not(unix))]
+    let cancel = &AtomicBool::new(false);
+    let copied = storage::copy_profile_dir(&source.id, &copy.id, cancel, |progress| {
+        if show_progress {
+            eprint!("\rCopying data: {}", progress);
+        }
+    });
+    #[cfg(unix)]
+    signals::stop_catching();
+    if show_progress {
+        eprintln!();
+    }
+    copied?;
+
+    let id = copy.id.clone();
+    let name = copy.name.clone();
+    let added = storage::update_profiles(|profiles| {
+        if profiles.iter().any(|p| p.name == copy.name) {
+            return Err(CliError::AlreadyExists(copy.name));
+        }
+        profiles.push(copy);
+        profile::sort_profiles(profiles);
+        Ok(())
+    });
+    if let Err(err) = added {
+        let _ = storage::remove_profile_dir(&id);
+        return Err(err);
+    }
+    println!("{}", name);
+    Ok(())
+}
+
+fn delete(name: &str, delete_data: bool) -> Result<(), CliError> {
+    let mut deleted = None;
+    storage::update_profiles::<CliError>(|profiles| {
+        let idx = find_profile(profiles, name)?;
+        deleted = Some(profiles.remove(idx));
+        Ok(())
+    })?;
+
+    if let Some(profile) = deleted {
+        if delete_data {
+            storage::remove_profile_dir(&profile.id)?;
+        } else if let Some(archived) = storage::archive_profile_dir(&profile.id)? {
+            println!("Data archived to {}", archived.display());
+        }
+    }
+    Ok(())
+}
+
+fn select(name: &str) -> Result<(), CliError> {
+    let profiles = storage::load_profiles()?;
+    let idx = find_profile(&profiles, name)?;
+    Ok(storage::save_selected_profile(&profiles[idx].id)?)
+}
+
+fn current() -> Result<(), CliError> {
+    let profiles = storage::load_profiles()?;
+    let selected = storage::load_selected_profile()?
+        .and_then(|id| profiles.iter().find(|p| p.id == id))
+        .ok_or(CliError::NoSelection)?;
+    println!("{}", selected.name);
+    Ok(())
+}
+
+fn list_backups() -> Result<(), CliError> {
+    for (i, backup) in storage::list_backups()?.iter().enumerate() {
+        println!(
+            "{}\t{}\t{} profile(s)",
+            i + 1,
+            backup.created_utc(),
+            backup.profile_count
+        );
+    }
+    Ok(())
+}
+
+fn restore_backup(number: usize) -> Result<(), CliError> {
+    let backups = storage::list_backups()?;
+    let backup = number
+        .checked_sub(1)
+        .and_then(|i| backups.get(i))
+        .ok_or(CliError::NoSuchBackup(number))?;
+    storage::restore_backup(backup)?;
+    Ok(())
+}
+
+fn convert(to: storage::Format) -> Result<(), CliError> {
+    storage::convert(to)?;
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::launch::LaunchError;
+
+/// What is needed to launch an application from its freedesktop `.desktop` file
+#[derive(Debug)]
+pub struct DesktopEntry {
+    /// The program followed by its arguments, with field codes expanded
+    pub exec: Vec<String>,
+    /// Working directory from `Path=`
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Find `<id>.desktop` in the `applications` folder of the XDG data dirs and
+/// read it
+pub fn load(id: &str) -> Result<DesktopEntry, LaunchError> {
+    let file_name = if id.ends_with(".desktop") {
+        id.to_string()
+    } else {
+        format!("{}.desktop", id)
+    };
+
+    let searched: Vec<PathBuf> = data_dirs()
+        .into_iter()
+        .map(|dir| dir.join("applications"))
+        .collect();
+    let path = searched
+        .iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| LaunchError::DesktopEntryNotFound {
+            file_name,
+            searched: searched.clone(),
+        })?;
+
+    let content = fs::read_to_string(&path).map_err(|e| LaunchError::Io(path.clone(), e))?;
+    parse(&path, &content).map_err(|reason| LaunchError::InvalidDesktopEntry { path, reason })
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, with the spec's defaults
+fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local/share")));
+    let data_dirs = env::var_os("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    data_home
+        .into_iter()
+        .chain(env::split_paths(&data_dirs))
+        .filter(|dir| dir.is_absolute())
+        .collect()
+}
+
+fn parse(path: &Path, content: &str) -> Result<DesktopEntry, String> {
+    let mut in_main_group = false;
+    let mut exec = None;
+    let mut working_dir = None;
+    let mut name = None;
+    let mut icon = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_main_group = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_main_group {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = unescape(value.trim());
+        match key.trim() {
+            "Exec" => exec = Some(value),
+            "Path" if !value.is_empty() => working_dir = Some(PathBuf::from(value)),
+            "Name" => name = Some(value),
+            "Icon" => icon = Some(value),
+            _ => {}
+        }
+    }
+
+    let exec = exec.ok_or("no Exec= key in the [Desktop Entry] group")?;
+    let mut args = Vec::new();
+    for arg in split_exec(&exec)? {
+        match arg.as_str() {
+            // No files or URLs are passed to the program
+            "%f" | "%F" | "%u" | "%U" => {}
+            "%i" => {
+                if let Some(icon) = &icon {
+                    args.push("--icon".to_string());
+                    args.push(icon.clone());
+                }
+            }
+            _ => args.push(expand_field_codes(&arg, name.as_deref(), path)),
+        }
+    }
+    if args.is_empty() {
+        return Err("Exec= is empty".to_string());
+    }
+
+    Ok(DesktopEntry {
+        exec: args,
+        working_dir,
+    })
+}
+
+/// Undo the escapes allowed in any string value (`\s`, `\n`, `\t`, `\r`, `\\`)
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split an `Exec=` value into arguments. Arguments are separated by spaces
+/// and may be double-quoted, with `"`, `` ` ``, `$` and `\` escaped by a
+/// backslash inside quotes.
+fn split_exec(exec: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => arg.push(escaped),
+                            None => return Err("unterminated escape in Exec=".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated quote in Exec=".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                arg.push(chars.next().unwrap_or('\\'));
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Expand the field codes that can appear inside an argument: `%c` (the
+/// application name), `%k` (the desktop file) and `%%`. Deprecated and
+/// unknown codes are removed.
+fn expand_field_codes(arg: &str, name: Option<&str>, path: &Path) -> String {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some('c') => expanded.push_str(name.unwrap_or_default()),
+            Some('k') => expanded.push_str(&path.to_string_lossy()),
+            _ => {}
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_exec_handles_quotes_and_escapes() {
+        assert_eq!(
+            split_exec(r#"app  "two words" "say \"hi\"" a\ b"#).unwrap(),
+            ["app", "two words", r#"say "hi""#, "a b"]
+        );
+        assert_eq!(split_exec(r#"app "" x"#).unwrap(), ["app", "", "x"]);
+        assert!(split_exec(r#"app "open"#).is_err());
+    }
+
+    #[test]
+    fn expand_field_codes_keeps_known_codes_only() {
+        let path = Path::new("/usr/share/applications/app.desktop");
+        assert_eq!(expand_field_codes("100%%", Some("App"), path), "100%");
+        assert_eq!(
+            expand_field_codes("--name=%c", Some("App"), path),
+            "--name=App"
+        );
+        assert_eq!(
+            expand_field_codes("%k", None, path),
+            "/usr/share/applications/app.desktop"
+        );
+        assert_eq!(expand_field_codes("a%db%v", None, path), "ab");
+    }
+
+    #[test]
+    fn unescape_handles_string_escapes() {
+        assert_eq!(unescape(r"a\sb\tc\\d"), "a b\tc\\d");
+        assert_eq!(unescape(r"keep\q"), r"keep\q");
+        assert_eq!(unescape("end\\"), "end\\");
+    }
+
+    #[test]
+    fn parse_reads_the_main_group() {
+        let content = r#"[Desktop Entry]
+# A comment
+Name=My App
+Icon=my-app
+Exec=my-app "--title=a \\"b\\"" --done=100%% %F %i
+Path=/opt/my-app
+
+[Desktop Action new]
+Exec=ignored
+"#;
+        let entry = parse(Path::new("my-app.desktop"), content).unwrap();
+        assert_eq!(
+            entry.exec,
+            [
+                "my-app",
+                r#"--title=a "b""#,
+                "--done=100%",
+                "--icon",
+                "my-app"
+            ]
+        );
+        assert_eq!(entry.working_dir, Some(PathBuf::from("/opt/my-app")));
+    }
+
+    #[test]
+    fn parse_requires_exec() {
+        let content = "[Desktop Entry]\nName=App\n[Other]\nExec=app\n";
+        assert!(parse(Path::new("app.desktop"), content).is_err());
+    }
+}
@@ -0,0 +1,780 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, ExitCode};
+
+use serde::Serialize;
+
+use crate::desktop;
+use crate::profile::Profile;
+#[cfg(unix)]
+use crate::signals;
+use crate::storage;
+
+/// Why the program cannot be launched
+#[derive(Debug)]
+pub enum LaunchError {
+    /// A bare program name was not found in any `PATH` directory
+    NotInPath {
+        program: PathBuf,
+        searched: Vec<PathBuf>,
+    },
+    NotFound(PathBuf),
+    NotAFile(PathBuf),
+    NotExecutable(PathBuf),
+    Io(PathBuf, io::Error),
+    DesktopEntryNotFound {
+        file_name: String,
+        searched: Vec<PathBuf>,
+    },
+    InvalidDesktopEntry {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl fmt::Display for LaunchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchError::NotInPath { program, searched } if searched.is_empty() => {
+                write!(f, "'{}' is not a path and PATH is empty", program.display())
+            }
+            LaunchError::NotInPath { program, searched } => write!(
+                f,
+                "'{}' was not found in PATH (searched {})",
+                program.display(),
+                join_paths(searched)
+            ),
+            LaunchError::NotFound(path) => write!(f, "{}: no such file", path.display()),
+            LaunchError::NotAFile(path) => write!(f, "{}: not a file", path.display()),
+            LaunchError::NotExecutable(path) => {
+                write!(
+                    f,
+                    "{}: not executable (check its permissions)",
+                    path.display()
+                )
+            }
+            LaunchError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            LaunchError::DesktopEntryNotFound {
+                file_name,
+                searched,
+            } => write!(f, "{} not found in {}", file_name, join_paths(searched)),
+            LaunchError::InvalidDesktopEntry { path, reason } => {
+                write!(f, "{}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LaunchError {}
+
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A value describing the launched profile that can be exported to the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProfileField {
+    Name,
+    Id,
+    /// The profile's data directory
+    Dir,
+    /// The app's config directory
+    ConfigDir,
+    AppId,
+}
+
+impl ProfileField {
+    const ALL: [ProfileField; 5] = [
+        ProfileField::Name,
+        ProfileField::Id,
+        ProfileField::Dir,
+        ProfileField::ConfigDir,
+        ProfileField::AppId,
+    ];
+
+    /// Variable name used after `--env-prefix`
+    fn suffix(self) -> &'static str {
+        match self {
+            ProfileField::Name => "NAME",
+            ProfileField::Id => "ID",
+            ProfileField::Dir => "DIR",
+            ProfileField::ConfigDir => "CONFIG_DIR",
+            ProfileField::AppId => "APP_ID",
+        }
+    }
+}
+
+/// Parse a `FIELD=VAR` mapping given to `--env`
+pub fn parse_env_mapping(mapping: &str) -> Result<(ProfileField, String), String> {
+    let (field, var) = mapping
+        .split_once('=')
+        .ok_or("expected FIELD=VAR, e.g. dir=MYAPP_DATA")?;
+    let field = <ProfileField as clap::ValueEnum>::from_str(field, true).map_err(|_| {
+        format!(
+            "unknown field '{}' (expected name, id, dir, config-dir or app-id)",
+            field
+        )
+    })?;
+    Ok((field, parse_var_name(var)?))
+}
+
+/// Parse an environment variable name (or `--env-prefix`), which `--print`
+/// writes unquoted: letters, digits and `_`, not starting with a digit
+pub fn parse_var_name(name: &str) -> Result<String, String> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!(
+            "'{}' is not a valid variable name (letters, digits and _, not starting with a digit)",
+            name
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Parse a dotfile given to `--pass-dotfile`: a path relative to the home
+/// directory, such as `.gitconfig` or `.config/git`
+pub fn parse_dotfile(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if path.as_os_str().is_empty() {
+        return Err("the path cannot be empty".to_string());
+    }
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err("expected a path relative to the home directory, without '..'".to_string());
+    }
+    Ok(path)
+}
+
+/// The environment variables to export: `--env-var` (and `<VAR>_ID`), every
+/// field under `--env-prefix`, then the explicit `--env` mappings
+pub fn exported_vars(
+    env_var: Option<&str>,
+    env_prefix: Option<&str>,
+    mappings: &[(ProfileField, String)],
+) -> Vec<(ProfileField, String)> {
+    let mut vars = Vec::new();
+    if let Some(env_var) = env_var {
+        vars.push((ProfileField::Name, env_var.to_string()));
+        vars.push((ProfileField::Id, format!("{}_ID", env_var)));
+    }
+    if let Some(prefix) = env_prefix {
+        vars.extend(
+            ProfileField::ALL
+                .into_iter()
+                .map(|field| (field, format!("{}{}", prefix, field.suffix()))),
+        );
+    }
+    vars.extend(mappings.iter().cloned());
+    vars
+}
+
+/// Whether `env` exports the profile's data directory
+pub fn exports_dir(env: &[(ProfileField, String)]) -> bool {
+    env.iter().any(|(field, _)| *field == ProfileField::Dir)
+}
+
+/// Everything the launched program is told about the selected profile
+#[derive(Debug, Serialize)]
+pub struct ProfileContext {
+    pub name: String,
+    pub id: String,
+    /// The profile's data directory, only looked up when it is used: an ID
+    /// that cannot name a folder must not stop a plain launch
+    pub dir: Option<PathBuf>,
+    pub config_dir: PathBuf,
+    pub app_id: String,
+}
+
+impl ProfileContext {
+    /// Gather the context of `profile`, creating its data directory when
+    /// `with_dir` is set
+    pub fn new(profile: &Profile, with_dir: bool) -> storage::Result<Self> {
+        let dir = if with_dir {
+            Some(storage::ensure_profile_dir(&profile.id)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            name: profile.name.clone(),
+            id: profile.id.clone(),
+            dir,
+            config_dir: storage::ensure_config_dir()?,
+            app_id: storage::get_app_id().to_string(),
+        })
+    }
+
+    /// The value of `field`; the data directory is empty unless the context
+    /// was gathered with it
+    pub fn value(&self, field: ProfileField) -> &OsStr {
+        match field {
+            ProfileField::Name => OsStr::new(&self.name),
+            ProfileField::Id => OsStr::new(&self.id),
+            ProfileField::Dir => self.dir.as_deref().map_or(OsStr::new(""), Path::as_os_str),
+            ProfileField::ConfigDir => self.config_dir.as_os_str(),
+            ProfileField::AppId => OsStr::new(&self.app_id),
+        }
+    }
+}
+
+/// Shell dialects for `--print`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    /// POSIX shells (sh, bash, zsh, ...)
+    Sh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    /// A statement exporting `var` set to `value`, quoted for this shell
+    pub fn export(self, var: &str, value: &str) -> String {
+        match self {
+            Shell::Sh => format!("export {}='{}'", var, value.replace('\'', r"'\''")),
+            Shell::Fish => format!(
+                "set -gx {} '{}'",
+                var,
+                value.replace('\\', r"\\").replace('\'', r"\'")
+            ),
+            Shell::Powershell => {
+                let mut quoted = String::with_capacity(value.len());
+                for c in value.chars() {
+                    if POWERSHELL_QUOTES.contains(&c) {
+                        quoted.push(c);
+                    }
+                    quoted.push(c);
+                }
+                format!("$env:{} = '{}'", var, quoted)
+            }
+        }
+    }
+}
+
+/// Characters PowerShell takes as a single quote, each escaped by doubling it
+const POWERSHELL_QUOTES: [char; 5] = ['\'', '\u{2018}', '\u{2019}', '\u{201A}', '\u{201B}'];
+
+/// How `--output` reports the selected profile on stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Output {
+    /// An object with the name, ID, data dir, config dir and app ID
+    Json,
+    /// The profile name alone
+    Name,
+    None,
+}
+
+impl Output {
+    pub fn print(self, context: &ProfileContext) {
+        match self {
+            Output::Json => match serde_json::to_string(context) {
+                Ok(json) => println!("{}", json),
+                Err(err) => eprintln!("Error: {}", err),
+            },
+            Output::Name => println!("{}", context.name),
+            Output::None => {}
+        }
+    }
+}
+
+/// Starts the program configured with `--program` or `--desktop-id` for the
+/// selected profile
+#[derive(Debug, Clone)]
+pub struct Launcher {
+    /// Environment variables set for the program (see `exported_vars`)
+    env: Vec<(ProfileField, String)>,
+    program: Option<PathBuf>,
+    desktop_id: Option<String>,
+    /// Arguments for the program, which may contain placeholders (see `expand`)
+    args: Vec<String>,
+    working_dir: Option<PathBuf>,
+    exec: bool,
+    /// Give the program its own `HOME` and XDG base directories inside the
+    /// profile's data directory (see `isolated_home`)
+    isolate_home: bool,
+    /// Paths relative to the real home linked into the isolated one
+    dotfiles: Vec<PathBuf>,
+}
+
+impl Launcher {
+    pub fn new(
+        env: Vec<(ProfileField, String)>,
+        program: Option<String>,
+        args: Vec<String>,
+    ) -> Self {
+        Self {
+            env,
+            program: program.map(PathBuf::from),
+            desktop_id: None,
+            args,
+            working_dir: None,
+            exec: false,
+            isolate_home: false,
+            dotfiles: Vec::new(),
+        }
+    }
+
+    /// Take the program, its arguments and working directory from a desktop
+    /// entry (see `resolve`)
+    pub fn with_desktop_id(mut self, desktop_id: Option<String>) -> Self {
+        self.desktop_id = desktop_id;
+        self
+    }
+
+    /// Replace the manager process with the program (see `exec`) instead of
+    /// spawning it from the window
+    #[cfg(unix)]
+    pub fn with_exec(mut self, exec: bool) -> Self {
+        self.exec = exec;
+        self
+    }
+
+    /// Point `HOME` and the XDG base directories at the profile's data
+    /// directory, passing through the given dotfiles from the real home
+    pub fn with_isolated_home(mut self, isolate_home: bool, dotfiles: Vec<PathBuf>) -> Self {
+        self.isolate_home = isolate_home;
+        self.dotfiles = dotfiles;
+        self
+    }
+
+    /// Whether the process is replaced with the program instead of spawning
+    /// it; only when a program is started at all (see `is_configured`)
+    pub fn replaces_process(&self) -> bool {
+        self.exec && self.is_configured()
+    }
+
+    /// Whether a program is started once a profile is selected
+    pub fn is_configured(&self) -> bool {
+        !self.env.is_empty() && self.program.is_some()
+    }
+
+    /// Whether the program is given the profile's data directory: exported,
+    /// as `{profile_dir}` or as its isolated home
+    fn uses_profile_dir(&self) -> bool {
+        self.isolate_home
+            || exports_dir(&self.env)
+            || self.args.iter().any(|arg| arg.contains("{profile_dir}"))
+    }
+
+    /// Read the desktop entry, if any, and find the program in `PATH`, so a
+    /// wrong `--program` is reported before the window opens
+    pub fn resolve(&mut self) -> Result<(), LaunchError> {
+        if let Some(desktop_id) = &self.desktop_id {
+            let entry = desktop::load(desktop_id)?;
+            let mut exec = entry.exec.into_iter();
+            self.program = exec.next().map(PathBuf::from);
+            // Arguments given after `--` follow the ones from Exec=
+            self.args = exec.chain(mem::take(&mut self.args)).collect();
+            self.working_dir = entry.working_dir;
+        }
+
+        if let Some(program) = &self.program {
+            self.program = Some(find_program(program)?);
+        }
+        Ok(())
+    }
+
+    pub fn spawn(&self, profile: &Profile) -> io::Result<Child> {
+        let mut command = self.command(profile)?;
+        command
+            .spawn()
+            .map_err(|e| program_error(command.get_program().to_string_lossy().as_ref(), e))
+    }
+
+    /// Replace the current process with the program, keeping its PID. Only
+    /// returns on failure.
+    #[cfg(unix)]
+    pub fn exec(&self, profile: &Profile) -> io::Error {
+        use std::os::unix::process::CommandExt;
+
+        match self.command(profile) {
+            Ok(mut command) => command.exec(),
+            Err(err) => err,
+        }
+    }
+
+    fn command(&self, profile: &Profile) -> io::Result<Command> {
+        let Some(program) = &self.program else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no program to launch",
+            ));
+        };
+        let context = ProfileContext::new(profile, self.uses_profile_dir())
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        let mut command = Command::new(program);
+        command.args(self.args.iter().map(|arg| expand(arg, &context)));
+        for (field, var) in &self.env {
+            command.env(var, context.value(*field));
+        }
+        if let Some(profile_dir) = context.dir.as_deref().filter(|_| self.isolate_home) {
+            let home = isolated_home(profile_dir, &self.dotfiles)?;
+            for (var, dir) in [
+                ("XDG_CONFIG_HOME", ".config"),
+                ("XDG_DATA_HOME", ".local/share"),
+                ("XDG_CACHE_HOME", ".cache"),
+                ("XDG_STATE_HOME", ".local/state"),
+            ] {
+                command.env(var, home.join(dir));
+            }
+            command.env("HOME", home);
+        }
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+        Ok(command)
+    }
+}
+
+/// Create `<profile dir>/home` with the usual XDG layout and link each
+/// dotfile from the real home into it. Dotfiles already present in the
+/// isolated home are left alone; missing ones are skipped with a warning.
+fn isolated_home(profile_dir: &Path, dotfiles: &[PathBuf]) -> io::Result<PathBuf> {
+    let home = profile_dir.join("home");
+    for dir in [".config", ".local/share", ".cache", ".local/state"] {
+        fs::create_dir_all(home.join(dir))?;
+    }
+    if dotfiles.is_empty() {
+        return Ok(home);
+    }
+
+    let Some(real_home) = dirs::home_dir() else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not determine the home directory to pass dotfiles from",
+        ));
+    };
+    for dotfile in dotfiles {
+        let source = real_home.join(dotfile);
+        let target = home.join(dotfile);
+        if target.symlink_metadata().is_ok() {
+            continue;
+        }
+        if source.symlink_metadata().is_err() {
+            eprintln!(
+                "Warning: {} does not exist, not passing it through",
+                source.display()
+            );
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        link(&source, &target).map_err(|e| {
+            io::Error::new(e.kind(), format!("cannot link {}: {}", target.display(), e))
+        })?;
+    }
+    Ok(home)
+}
+
+#[cfg(unix)]
+fn link(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+fn link(source: &Path, target: &Path) -> io::Result<()> {
+    if source.is_dir() {
+        std::os::windows::fs::symlink_dir(source, target)
+    } else {
+        std::os::windows::fs::symlink_file(source, target)
+    }
+}
+
+/// Look up a bare program name in `PATH`; paths are only checked
+fn find_program(program: &Path) -> Result<PathBuf, LaunchError> {
+    if program.components().count() > 1 {
+        check_executable(program)?;
+        return Ok(program.to_path_buf());
+    }
+
+    let searched: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    for dir in &searched {
+        let candidate = dir.join(program);
+        if check_executable(&candidate).is_ok() {
+            return Ok(candidate);
+        }
+        #[cfg(windows)]
+        if candidate.extension().is_none() {
+            let candidate = candidate.with_extension("exe");
+            if check_executable(&candidate).is_ok() {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    Err(LaunchError::NotInPath {
+        program: program.to_path_buf(),
+        searched,
+    })
+}
+
+fn check_executable(path: &Path) -> Result<(), LaunchError> {
+    let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => LaunchError::NotFound(path.to_path_buf()),
+        _ => LaunchError::Io(path.to_path_buf(), e),
+    })?;
+    if !metadata.is_file() {
+        return Err(LaunchError::NotAFile(path.to_path_buf()));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(LaunchError::NotExecutable(path.to_path_buf()));
+        }
+    }
+    Ok(())
+}
+
+/// Prefix an error with the program it is about, keeping its kind
+fn program_error(program: &str, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", program, err))
+}
+
+/// Replace `{profile}`, `{profile_id}` and `{profile_dir}` in a program
+/// argument. Unknown placeholders are left as they are.
+fn expand(arg: &str, context: &ProfileContext) -> String {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let value = match &rest[1..end] {
+            "profile" => context.name.clone(),
+            "profile_id" => context.id.clone(),
+            "profile_dir" => context
+                .dir
+                .as_deref()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            _ => {
+                expanded.push('{');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Wait for `child` to exit, forwarding SIGINT and SIGTERM to it, and return
+/// its exit status. A child killed by a signal yields `128 + signal`, as in
+/// shells.
+pub fn wait(mut child: Child) -> ExitCode {
+    #[cfg(unix)]
+    signals::forward_to(&child);
+
+    let status = child.wait();
+
+    #[cfg(unix)]
+    signals::stop_forwarding();
+
+    let status = match status {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("Error: failed to wait for the program: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(code) = status.code() {
+        return ExitCode::from(code as u8);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return ExitCode::from(128u8.wrapping_add(signal as u8));
+        }
+    }
+    ExitCode::FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> ProfileContext {
+        ProfileContext {
+            name: "Work".to_string(),
+            id: "42".to_string(),
+            dir: Some(PathBuf::from("/data/42")),
+            config_dir: PathBuf::from("/data"),
+            app_id: "app".to_string(),
+        }
+    }
+
+    #[test]
+    fn data_dir_is_only_required_when_used() {
+        let dir = storage::TestConfigDir::new();
+        let profile = Profile {
+            id: "work.v2".to_string(),
+            name: "Work".to_string(),
+        };
+
+        let context = ProfileContext::new(&profile, false).unwrap();
+        assert_eq!(context.value(ProfileField::Id), "work.v2");
+        assert!(context.dir.is_none());
+        assert!(!dir.path.join("profiles").exists());
+        assert!(ProfileContext::new(&profile, true).is_err());
+
+        let launcher = Launcher::new(
+            exported_vars(Some("V"), None, &[]),
+            Some("true".to_string()),
+            vec!["--name={profile}".to_string()],
+        );
+        assert!(!launcher.uses_profile_dir());
+        assert!(launcher
+            .with_isolated_home(true, Vec::new())
+            .uses_profile_dir());
+        let launcher = Launcher::new(
+            exported_vars(None, Some("P_"), &[]),
+            Some("true".to_string()),
+            Vec::new(),
+        );
+        assert!(launcher.uses_profile_dir());
+    }
+
+    #[test]
+    fn expand_replaces_placeholders() {
+        let context = context();
+        assert_eq!(expand("--profile={profile}", &context), "--profile=Work");
+        assert_eq!(expand("{profile_id}", &context), "42");
+        assert_eq!(expand("{profile_dir}/x", &context), "/data/42/x");
+        assert_eq!(expand("{profile}-{profile_id}", &context), "Work-42");
+    }
+
+    #[test]
+    fn expand_keeps_unknown_and_unclosed_braces() {
+        let context = context();
+        assert_eq!(expand("{unknown}", &context), "{unknown}");
+        assert_eq!(expand("{profile", &context), "{profile");
+        assert_eq!(expand("{{profile}}", &context), "{Work}");
+        assert_eq!(expand("plain", &context), "plain");
+    }
+
+    #[test]
+    fn export_quotes_single_quotes_for_each_shell() {
+        assert_eq!(Shell::Sh.export("V", "it's"), r"export V='it'\''s'");
+        assert_eq!(Shell::Fish.export("V", "it's"), r"set -gx V 'it\'s'");
+        assert_eq!(Shell::Powershell.export("V", "it's"), "$env:V = 'it''s'");
+        assert_eq!(
+            Shell::Powershell.export("V", "Bob\u{2019}s; \u{2018}x\u{201A}\u{201B}"),
+            "$env:V = 'Bob\u{2019}\u{2019}s; \u{2018}\u{2018}x\u{201A}\u{201A}\u{201B}\u{201B}'"
+        );
+    }
+
+    #[test]
+    fn export_escapes_backslashes_for_fish_only() {
+        assert_eq!(Shell::Sh.export("V", r"a\b"), r"export V='a\b'");
+        assert_eq!(Shell::Fish.export("V", r"a\b"), r"set -gx V 'a\\b'");
+        assert_eq!(Shell::Powershell.export("V", r"a\b"), r"$env:V = 'a\b'");
+    }
+
+    #[test]
+    fn parse_var_name_rejects_unsafe_names() {
+        assert_eq!(parse_var_name("MY_APP2").as_deref(), Ok("MY_APP2"));
+        assert_eq!(parse_var_name("_x").as_deref(), Ok("_x"));
+        for name in ["", "2X", "A-B", "A B", "X;rm", "$(id)"] {
+            assert!(parse_var_name(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn exported_vars_combine_every_flag_in_order() {
+        let vars = exported_vars(
+            Some("APP"),
+            Some("P_"),
+            &[(ProfileField::Dir, "DATA".to_string())],
+        );
+        assert_eq!(
+            vars,
+            [
+                (ProfileField::Name, "APP".to_string()),
+                (ProfileField::Id, "APP_ID".to_string()),
+                (ProfileField::Name, "P_NAME".to_string()),
+                (ProfileField::Id, "P_ID".to_string()),
+                (ProfileField::Dir, "P_DIR".to_string()),
+                (ProfileField::ConfigDir, "P_CONFIG_DIR".to_string()),
+                (ProfileField::AppId, "P_APP_ID".to_string()),
+                (ProfileField::Dir, "DATA".to_string()),
+            ]
+        );
+        assert!(exported_vars(None, None, &[]).is_empty());
+    }
+
+    #[test]
+    fn parse_env_mapping_reads_field_and_name() {
+        assert_eq!(
+            parse_env_mapping("dir=MYAPP_DATA"),
+            Ok((ProfileField::Dir, "MYAPP_DATA".to_string()))
+        );
+        assert_eq!(
+            parse_env_mapping("config-dir=CFG"),
+            Ok((ProfileField::ConfigDir, "CFG".to_string()))
+        );
+        assert_eq!(
+            parse_env_mapping("App-Id=A"),
+            Ok((ProfileField::AppId, "A".to_string()))
+        );
+        for mapping in ["dir", "home=H", "=V", "dir=", "dir=MY-DATA", "name=X=Y"] {
+            assert!(
+                parse_env_mapping(mapping).is_err(),
+                "{:?} was accepted",
+                mapping
+            );
+        }
+    }
+
+    #[test]
+    fn parse_dotfile_accepts_paths_inside_home() {
+        assert_eq!(parse_dotfile(".gitconfig"), Ok(PathBuf::from(".gitconfig")));
+        assert_eq!(
+            parse_dotfile(".config/git"),
+            Ok(PathBuf::from(".config/git"))
+        );
+    }
+
+    #[test]
+    fn parse_dotfile_rejects_paths_leaving_home() {
+        for path in [
+            "",
+            ".",
+            "..",
+            "../.ssh",
+            ".config/../../x",
+            "./.ssh",
+            "/etc/passwd",
+        ] {
+            assert!(parse_dotfile(path).is_err(), "{:?} was accepted", path);
+        }
+    }
+}
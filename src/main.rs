@@ -1,14 +1,32 @@
 mod app;
+mod cli;
+mod desktop;
+mod launch;
 mod profile;
+#[cfg(unix)]
+mod signals;
 mod storage;
+mod watcher;
 
-use app::ProfileApp;
-use clap::Parser;
+use std::cell::RefCell;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::Duration;
+
+use app::{Outcome, ProfileApp};
+use clap::{ArgGroup, Parser};
 use eframe::egui;
+use launch::{Launcher, Output, ProfileContext, ProfileField, Shell};
+use profile::Profile;
+use storage::StorageError;
 
 #[derive(Parser, Debug)]
 #[command(name = "simple-profiles-manager")]
 #[command(about = "A simple profile manager for applications")]
+#[command(group(ArgGroup::new("launch").args(["program", "desktop_id"]).requires("exports")))]
+#[command(group(ArgGroup::new("exports").args(["env_var", "env_prefix", "env"]).multiple(true)))]
 struct Args {
     /// Application ID (used for storage directory)
     #[arg(short, long)]
@@ -19,20 +37,293 @@ struct Args {
     title: Option<String>,
 
     /// Environment variable name to set with the selected profile name
-    #[arg(short, long)]
+    #[arg(short, long, value_parser = launch::parse_var_name)]
     env_var: Option<String>,
 
-    /// Program to execute after profile selection (full path)
+    /// Export the profile as a family of environment variables: <PREFIX>NAME,
+    /// <PREFIX>ID, <PREFIX>DIR, <PREFIX>CONFIG_DIR and <PREFIX>APP_ID
+    #[arg(long, value_name = "PREFIX", value_parser = launch::parse_var_name)]
+    env_prefix: Option<String>,
+
+    /// Export one profile value under the given name, e.g. dir=MYAPP_DATA
+    /// (fields: name, id, dir, config-dir, app-id); can be repeated
+    #[arg(long = "env", value_name = "FIELD=VAR", value_parser = launch::parse_env_mapping)]
+    env: Vec<(ProfileField, String)>,
+
+    /// Program to execute after profile selection (a path, or a name looked
+    /// up in PATH); requires --env-var, --env-prefix or --env
     #[arg(short, long)]
     program: Option<String>,
+
+    /// Launch the application described by this desktop entry ID (e.g.
+    /// org.example.App), read from the XDG data dirs, instead of --program
+    #[arg(long, value_name = "ID")]
+    desktop_id: Option<String>,
+
+    /// Skip the picker and use this profile (name or ID); when it does not
+    /// exist, the picker opens to create it
+    #[arg(long, value_name = "NAME_OR_ID")]
+    profile: Option<String>,
+
+    /// Create the --profile profile instead of opening the picker when it
+    /// does not exist
+    #[arg(long, requires = "profile")]
+    create_if_missing: bool,
+
+    /// Choose the last selected profile automatically after this many
+    /// seconds, unless a key is pressed or the window is clicked (only when a
+    /// profile was selected before)
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Keep running until the launched program exits, forwarding SIGINT and
+    /// SIGTERM to it, and exit with its status
+    #[arg(long, requires = "launch")]
+    wait: bool,
+
+    /// Replace this process with the program once a profile is selected,
+    /// so it keeps the manager's PID
+    #[cfg(unix)]
+    #[arg(long, requires = "launch", conflicts_with = "wait")]
+    exec: bool,
+
+    /// Set HOME, XDG_CONFIG_HOME, XDG_DATA_HOME, XDG_CACHE_HOME and
+    /// XDG_STATE_HOME to folders under the profile's data directory for the
+    /// launched program
+    #[arg(long, requires = "launch")]
+    isolate_home: bool,
+
+    /// Link this file or folder from the real home into the isolated one,
+    /// e.g. .gitconfig or .ssh; can be repeated
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "isolate_home",
+        value_parser = launch::parse_dotfile
+    )]
+    pass_dotfile: Vec<PathBuf>,
+
+    /// Base directory for profile data, replacing the platform config dir
+    /// (also read from SIMPLE_PROFILES_MANAGER_DIR)
+    #[arg(long, global = true)]
+    config_dir: Option<PathBuf>,
+
+    /// Store profile data next to the executable
+    #[arg(long, global = true, conflicts_with = "config_dir")]
+    portable: bool,
+
+    /// Number of previous profile lists kept in backups/ (0 disables, default 10)
+    #[arg(long, global = true, value_name = "COUNT")]
+    backups: Option<usize>,
+
+    /// Storage backend for profile data
+    #[arg(long, global = true, value_enum, default_value = "file")]
+    backend: storage::Backend,
+
+    /// Instead of launching a program, print the selected profile's variables
+    /// as shell assignments, for `eval "$(simple-profiles-manager ... --print)"`
+    #[arg(
+        long,
+        value_name = "SHELL",
+        num_args = 0..=1,
+        default_missing_value = "sh",
+        requires = "exports",
+        conflicts_with = "launch"
+    )]
+    print: Option<Shell>,
+
+    /// Write the selected profile to stdout once the window closes; exits
+    /// with 0 when a profile was chosen, 5 when the window was closed
+    /// without choosing, 1 on errors
+    #[arg(long, value_enum, default_value = "none", conflicts_with = "print")]
+    output: Output,
+
+    /// Arguments passed to the program, after `--`. `{profile}`, `{profile_id}`
+    /// and `{profile_dir}` are replaced with the selected profile's values.
+    #[arg(last = true, value_name = "PROGRAM_ARGS", requires = "launch")]
+    program_args: Vec<String>,
+
+    /// Manage profiles from the command line instead of opening the window
+    #[command(subcommand)]
+    command: Option<cli::Command>,
 }
 
-fn main() -> eframe::Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
-    let app_title = args.title.unwrap_or_else(|| args.app_id.clone());
 
     storage::set_app_id(&args.app_id);
 
+    if let Some(dir) = &args.config_dir {
+        // Paths handed to the program must not depend on its working directory
+        let Ok(dir) = std::path::absolute(dir) else {
+            eprintln!("Error: could not resolve --config-dir {}", dir.display());
+            return ExitCode::FAILURE;
+        };
+        storage::set_base_dir(dir);
+    } else if args.portable {
+        let Some(dir) = storage::portable_base_dir() else {
+            eprintln!("Error: could not determine the executable location for --portable");
+            return ExitCode::FAILURE;
+        };
+        storage::set_base_dir(dir);
+    }
+
+    if let Some(count) = args.backups {
+        storage::set_backup_retention(count);
+    }
+
+    if let Err(err) = storage::set_backend(args.backend) {
+        eprintln!("Error: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(command) = args.command {
+        return cli::run(command);
+    }
+
+    let env = launch::exported_vars(
+        args.env_var.as_deref(),
+        args.env_prefix.as_deref(),
+        &args.env,
+    );
+    let print = args.print;
+    let output = args.output;
+    let mut launcher = Launcher::new(env.clone(), args.program.clone(), args.program_args.clone())
+        .with_desktop_id(args.desktop_id.clone())
+        .with_isolated_home(args.isolate_home, args.pass_dotfile.clone());
+    #[cfg(unix)]
+    {
+        launcher = launcher.with_exec(args.exec);
+    }
+    let wait = args.wait;
+
+    if let Err(err) = launcher.resolve() {
+        eprintln!("Error: cannot launch the program: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    let mut missing_profile = None;
+    let selected = match &args.profile {
+        Some(key) => match select_profile(key, args.create_if_missing) {
+            Ok(Some(profile)) => Some(profile),
+            Ok(None) => {
+                missing_profile = Some(key.clone());
+                None
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    let outcome = match selected {
+        Some(profile) => match launch_headless(&launcher, profile) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                eprintln!("Error: failed to run the program: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => match run_gui(args, launcher.clone(), missing_profile) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let Some(profile) = &outcome.profile else {
+        return ExitCode::from(cli::EXIT_CANCELLED);
+    };
+    let with_dir = output == Output::Json || (print.is_some() && launch::exports_dir(&env));
+    let context = match ProfileContext::new(profile, with_dir) {
+        Ok(context) => context,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    output.print(&context);
+
+    if let Some(shell) = print {
+        for (field, var) in &env {
+            println!(
+                "{}",
+                shell.export(var, &context.value(*field).to_string_lossy())
+            );
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    #[cfg(unix)]
+    if launcher.replaces_process() {
+        let err = launcher.exec(profile);
+        eprintln!("Error: failed to run the program: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    match outcome.child {
+        Some(child) if wait => launch::wait(child),
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// Find the profile given with `--profile`, creating it when asked to, and
+/// store it as the selected one. `None` when it does not exist.
+fn select_profile(key: &str, create_if_missing: bool) -> storage::Result<Option<Profile>> {
+    let key = key.trim();
+    let mut profiles = storage::load_profiles()?;
+    if profile::find_profile(&profiles, key).is_none() {
+        if !create_if_missing || key.is_empty() {
+            return Ok(None);
+        }
+        profiles = storage::update_profiles(|profiles| {
+            if profile::find_profile(profiles, key).is_none() {
+                profiles.push(Profile::new(key.to_string()));
+                profile::sort_profiles(profiles);
+            }
+            Ok::<_, StorageError>(())
+        })?;
+    }
+
+    let Some(idx) = profile::find_profile(&profiles, key) else {
+        return Ok(None);
+    };
+    let profile = profiles.swap_remove(idx);
+    storage::save_selected_profile(&profile.id)?;
+    Ok(Some(profile))
+}
+
+/// Launch `profile` without opening the window
+fn launch_headless(launcher: &Launcher, profile: Profile) -> io::Result<Outcome> {
+    // With --exec, the process is replaced with the program further on
+    let child = if launcher.is_configured() && !launcher.replaces_process() {
+        Some(launcher.spawn(&profile)?)
+    } else {
+        None
+    };
+    Ok(Outcome {
+        profile: Some(profile),
+        child,
+    })
+}
+
+/// Run the window until it closes. `missing_profile` is a `--profile` that
+/// does not exist, offered for creation.
+fn run_gui(
+    args: Args,
+    launcher: Launcher,
+    missing_profile: Option<String>,
+) -> eframe::Result<Outcome> {
+    let outcome = Rc::new(RefCell::new(Outcome::default()));
+    let app_outcome = Rc::clone(&outcome);
+    let close_on_select = args.print.is_some() || args.output != Output::None;
+    let timeout = args.timeout.map(Duration::from_secs);
+    let app_title = args.title.unwrap_or_else(|| args.app_id.clone());
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([400.0, 350.0])
@@ -43,12 +334,22 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         &format!("Profile Manager - {}", app_title),
         options,
-        Box::new(move |_cc| {
-            Ok(Box::new(ProfileApp::new(
-                app_title.clone(),
-                args.env_var.clone(),
-                args.program.clone(),
-            )))
+        Box::new(move |cc| {
+            let mut app = ProfileApp::new(
+                cc.egui_ctx.clone(),
+                app_title,
+                launcher,
+                app_outcome,
+                close_on_select,
+            );
+            if let Some(name) = missing_profile {
+                app.offer_to_create(name);
+            } else if let Some(timeout) = timeout {
+                app.start_countdown(timeout);
+            }
+            Ok(Box::new(app))
         }),
-    )
+    )?;
+
+    Ok(outcome.take())
 }
@@ -1,12 +1,77 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Profile {
+    /// Immutable identifier, stays the same when the profile is renamed
+    pub id: String,
     pub name: String,
 }
 
 impl Profile {
     pub fn new(name: String) -> Self {
-        Self { name }
+        Self { id: new_id(), name }
+    }
+
+    /// A copy of this profile's metadata under a new ID and name
+    pub fn duplicate(&self, name: String) -> Self {
+        let mut copy = self.clone();
+        copy.id = new_id();
+        copy.name = name;
+        copy
+    }
+}
+
+pub fn new_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Sort profiles alphabetically, ignoring case
+pub fn sort_profiles(profiles: &mut [Profile]) {
+    profiles.sort_by_key(|p| p.name.to_lowercase());
+}
+
+/// Find a profile by ID, falling back to its name
+pub fn find_profile(profiles: &[Profile], key: &str) -> Option<usize> {
+    profiles
+        .iter()
+        .position(|p| p.id == key)
+        .or_else(|| profiles.iter().position(|p| p.name == key))
+}
+
+/// A name for a copy of `name` that no profile uses yet: "<name> (copy)",
+/// then "<name> (copy 2)", "<name> (copy 3)", ...
+pub fn copy_name(profiles: &[Profile], name: &str) -> String {
+    let mut candidate = format!("{} (copy)", name);
+    let mut n = 2;
+    while profiles.iter().any(|p| p.name == candidate) {
+        candidate = format!("{} (copy {})", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_name_skips_taken_names() {
+        let mut profiles = vec![Profile::new("A".to_string())];
+        assert_eq!(copy_name(&profiles, "A"), "A (copy)");
+
+        profiles.push(Profile::new("A (copy)".to_string()));
+        assert_eq!(copy_name(&profiles, "A"), "A (copy 2)");
+
+        profiles.push(Profile::new("A (copy 2)".to_string()));
+        assert_eq!(copy_name(&profiles, "A"), "A (copy 3)");
+    }
+
+    #[test]
+    fn duplicate_gets_a_new_id() {
+        let profile = Profile::new("A".to_string());
+        let copy = profile.duplicate("A (copy)".to_string());
+        assert_eq!(copy.name, "A (copy)");
+        assert_ne!(copy.id, profile.id);
     }
 }
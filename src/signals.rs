@@ -0,0 +1,106 @@
+//! SIGINT and SIGTERM handling, shared by `--wait` (forwarded to the program)
+//! and `duplicate` (stops the copy cleanly)
+
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+
+const HANDLED: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// Dispositions that were in place before `install`, put back by `restore`
+static PREVIOUS: [AtomicUsize; 2] = [
+    AtomicUsize::new(libc::SIG_DFL),
+    AtomicUsize::new(libc::SIG_DFL),
+];
+
+/// PID of the child receiving forwarded signals, 0 when there is none
+static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Set while the child is in the terminal's foreground process group, where
+/// Ctrl-C already reaches it: a second SIGINT would make many programs force
+/// quit
+static CHILD_GETS_TERMINAL_SIGINT: AtomicBool = AtomicBool::new(false);
+
+/// Set when a signal arrives while they are caught
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn forward(signal: libc::c_int) {
+    if signal == libc::SIGINT && CHILD_GETS_TERMINAL_SIGINT.load(Ordering::SeqCst) {
+        return;
+    }
+    let pid = CHILD_PID.load(Ordering::SeqCst);
+    if pid > 0 {
+        // SAFETY: kill is async-signal-safe
+        unsafe {
+            libc::kill(pid, signal);
+        }
+    }
+}
+
+extern "C" fn interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Handle SIGINT and SIGTERM with `handler`, saving the current dispositions
+/// for `restore`. Ignored signals (e.g. in a background job) stay ignored.
+fn install(handler: extern "C" fn(libc::c_int)) {
+    for (signal, previous) in HANDLED.into_iter().zip(&PREVIOUS) {
+        // SAFETY: the handlers only touch atomics and call kill
+        let old = unsafe { libc::signal(signal, handler as libc::sighandler_t) };
+        if old == libc::SIG_IGN {
+            // SAFETY: puts back the disposition that was just replaced
+            unsafe {
+                libc::signal(signal, libc::SIG_IGN);
+            }
+        }
+        previous.store(old, Ordering::SeqCst);
+    }
+}
+
+/// Put back the dispositions saved by `install`
+fn restore() {
+    for (signal, previous) in HANDLED.into_iter().zip(&PREVIOUS) {
+        // SAFETY: reinstates a disposition the process had before
+        unsafe {
+            libc::signal(signal, previous.load(Ordering::SeqCst));
+        }
+    }
+}
+
+/// Whether this process (and so the child, which stays in its process group)
+/// is in the foreground of its controlling terminal
+fn in_terminal_foreground() -> bool {
+    let Ok(tty) = File::open("/dev/tty") else {
+        return false;
+    };
+    // SAFETY: plain queries on a valid descriptor
+    unsafe { libc::tcgetpgrp(tty.as_raw_fd()) == libc::getpgrp() }
+}
+
+/// Forward SIGINT and SIGTERM to `child` until `stop_forwarding`. A SIGINT
+/// while the child is in the terminal's foreground is not forwarded, as the
+/// terminal has sent it to the child already.
+pub fn forward_to(child: &Child) {
+    CHILD_GETS_TERMINAL_SIGINT.store(in_terminal_foreground(), Ordering::SeqCst);
+    CHILD_PID.store(child.id() as i32, Ordering::SeqCst);
+    install(forward);
+}
+
+/// Stop forwarding once the child is gone, so its PID can be reused safely
+pub fn stop_forwarding() {
+    restore();
+    CHILD_PID.store(0, Ordering::SeqCst);
+}
+
+/// Set the returned flag on SIGINT and SIGTERM instead of exiting, until
+/// `stop_catching`
+pub fn catch() -> &'static AtomicBool {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    install(interrupt);
+    &INTERRUPTED
+}
+
+pub fn stop_catching() {
+    restore();
+}
@@ -0,0 +1,1194 @@
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serde_norway::{Mapping, Value as YamlValue};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table};
+
+use super::{
+    backup_retention, ensure_config_dir, get_config_dir, Backup, ProfileStore, Result, StorageError,
+};
+use crate::profile::{self, Profile};
+
+const SELECTED_FILE: &str = "selected-profile";
+const LOCK_FILE: &str = ".lock";
+const BACKUPS_DIR: &str = "backups";
+
+/// Current version of the profiles file format
+const SCHEMA_VERSION: u64 = 2;
+
+/// On-disk layout of the profiles file
+#[derive(Serialize, Deserialize)]
+struct ProfilesDocument<'a> {
+    version: u64,
+    profiles: Cow<'a, [Profile]>,
+}
+
+/// On-disk formats of the profiles file. The format in use is the one of the
+/// `profiles.<extension>` file present in the config directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    const ALL: [Format; 3] = [Format::Json, Format::Toml, Format::Yaml];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+        }
+    }
+
+    fn profiles_file(self) -> String {
+        format!("profiles.{}", self.extension())
+    }
+
+    fn last_good_file(self) -> String {
+        format!("profiles.{}.bak", self.extension())
+    }
+
+    fn from_extension(extension: &str) -> Option<Format> {
+        Format::ALL.into_iter().find(|f| f.extension() == extension)
+    }
+
+    fn serialize(self, document: &ProfilesDocument) -> std::result::Result<String, String> {
+        match self {
+            Format::Json => serde_json::to_string_pretty(document).map_err(|e| e.to_string()),
+            Format::Toml => toml::to_string_pretty(document).map_err(|e| e.to_string()),
+            Format::Yaml => serde_norway::to_string(document).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn parse(self, content: &str) -> std::result::Result<Value, String> {
+        match self {
+            Format::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            Format::Yaml => serde_norway::from_str(content).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Serialize `profiles` to replace `previous`, keeping its comments where
+    /// possible: a TOML file is edited in place, so every comment stays; a
+    /// YAML file keeps its leading comment block and the comment lines right
+    /// above each profile. JSON has no comments. TOML and YAML files also keep
+    /// the keys the manager does not know.
+    fn update(self, previous: &str, profiles: &[Profile]) -> std::result::Result<String, String> {
+        let document = ProfilesDocument {
+            version: SCHEMA_VERSION,
+            profiles: Cow::Borrowed(profiles),
+        };
+        match self {
+            Format::Json => self.serialize(&document),
+            Format::Toml => match previous.parse::<DocumentMut>() {
+                Ok(edited) => update_toml(edited, profiles),
+                Err(_) => self.serialize(&document),
+            },
+            Format::Yaml => {
+                let body = match serde_norway::from_str(previous) {
+                    Ok(YamlValue::Mapping(edited)) => update_yaml(edited, profiles)?,
+                    _ => self.serialize(&document)?,
+                };
+                Ok(keep_yaml_comments(previous, &body))
+            }
+        }
+    }
+
+    /// The `#` comment lines at the top of a file, kept when the file is
+    /// converted to another format. JSON has no comments.
+    fn comment_header(self, content: &str) -> String {
+        if self == Format::Json {
+            return String::new();
+        }
+        let mut header = String::new();
+        for line in content.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                break;
+            }
+            header.push_str(line);
+        }
+        if !header.is_empty() && !header.ends_with('\n') {
+            header.push('\n');
+        }
+        header
+    }
+}
+
+/// Apply `profiles` to a parsed TOML profiles file. Tables of profiles that
+/// still exist are updated in place, keeping their comments and any keys the
+/// manager does not know; new profiles get new tables.
+fn update_toml(
+    mut edited: DocumentMut,
+    profiles: &[Profile],
+) -> std::result::Result<String, String> {
+    set_toml_value(
+        edited.as_table_mut(),
+        "version",
+        &Item::from(SCHEMA_VERSION as i64),
+    );
+
+    let mut existing: Vec<Table> = match edited.get("profiles") {
+        Some(Item::ArrayOfTables(tables)) => tables.iter().cloned().collect(),
+        _ => Vec::new(),
+    };
+    let mut tables = ArrayOfTables::new();
+    for profile in profiles {
+        let fresh = toml::to_string(profile)
+            .map_err(|e| e.to_string())?
+            .parse::<DocumentMut>()
+            .map_err(|e| e.to_string())?;
+        // A table added by hand without an ID is the profile of the same name
+        let position = existing
+            .iter()
+            .position(|table| table.get("id").and_then(Item::as_str) == Some(&profile.id))
+            .or_else(|| {
+                existing.iter().position(|table| {
+                    !table.contains_key("id")
+                        && table.get("name").and_then(Item::as_str) == Some(&profile.name)
+                })
+            });
+        let table = match position {
+            Some(i) => {
+                let mut table = existing.remove(i);
+                for (key, item) in fresh.iter() {
+                    set_toml_value(&mut table, key, item);
+                }
+                table
+            }
+            None => fresh.as_table().clone(),
+        };
+        tables.push(table);
+    }
+
+    if tables.is_empty() {
+        set_toml_value(edited.as_table_mut(), "profiles", &Item::from(Array::new()));
+    } else {
+        edited.insert("profiles", Item::ArrayOfTables(tables));
+    }
+    Ok(edited.to_string())
+}
+
+/// Apply `profiles` to a parsed YAML profiles file, the way `update_toml`
+/// does: the mappings of profiles that still exist keep the keys the manager
+/// does not know, after their `id` and `name`.
+fn update_yaml(mut edited: Mapping, profiles: &[Profile]) -> std::result::Result<String, String> {
+    let mut existing: Vec<Mapping> = match edited.get("profiles") {
+        Some(YamlValue::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_mapping().cloned())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut items = Vec::with_capacity(profiles.len());
+    for profile in profiles {
+        // An entry added by hand without an ID is the profile of the same name
+        let position = existing
+            .iter()
+            .position(|item| item.get("id").and_then(YamlValue::as_str) == Some(&profile.id))
+            .or_else(|| {
+                existing.iter().position(|item| {
+                    !item.contains_key("id")
+                        && item.get("name").and_then(YamlValue::as_str) == Some(&profile.name)
+                })
+            });
+        let mut item = Mapping::new();
+        item.insert("id".into(), profile.id.as_str().into());
+        item.insert("name".into(), profile.name.as_str().into());
+        if let Some(i) = position {
+            for (key, value) in existing.remove(i) {
+                if !item.contains_key(&key) {
+                    item.insert(key, value);
+                }
+            }
+        }
+        items.push(YamlValue::Mapping(item));
+    }
+
+    edited.insert("version".into(), SCHEMA_VERSION.into());
+    edited.insert("profiles".into(), YamlValue::Sequence(items));
+    serde_norway::to_string(&edited).map_err(|e| e.to_string())
+}
+
+/// Set `key` in `table`, keeping the comments around an existing value
+fn set_toml_value(table: &mut Table, key: &str, item: &Item) {
+    match (
+        table.get_mut(key).and_then(Item::as_value_mut),
+        item.as_value(),
+    ) {
+        (Some(value), Some(new)) => {
+            if value.to_string().trim() != new.to_string().trim() {
+                let decor = value.decor().clone();
+                *value = new.clone();
+                *value.decor_mut() = decor;
+            }
+        }
+        _ => {
+            table.insert(key, item.clone());
+        }
+    }
+}
+
+/// Carry the comments of the YAML file `previous` over to its replacement
+/// `body`, as written by serde_norway: the leading comment block, and the
+/// comment lines right above each profile entry, matched by profile ID.
+/// End-of-line comments and comments elsewhere are not kept.
+fn keep_yaml_comments(previous: &str, body: &str) -> String {
+    let mut above_profiles: Vec<(&str, String)> = Vec::new();
+    let mut pending = String::new();
+    // Comments above the list entry being read, until its ID is found
+    let mut entry_comments: Option<String> = None;
+    let mut in_header = true;
+    for line in previous.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            if !in_header && (trimmed.starts_with('#') || !pending.is_empty()) {
+                pending.push_str(line);
+            }
+            continue;
+        }
+        in_header = false;
+        if trimmed == "-" || trimmed.starts_with("- ") {
+            entry_comments = Some(std::mem::take(&mut pending));
+        } else {
+            pending.clear();
+        }
+        if let Some(id) = yaml_profile_id(trimmed) {
+            if let Some(comments) = entry_comments.take().filter(|c| !c.is_empty()) {
+                above_profiles.push((id, comments));
+            }
+        }
+    }
+
+    let mut content = Format::Yaml.comment_header(previous);
+    for line in body.split_inclusive('\n') {
+        if let Some(id) = line.strip_prefix("- ").and_then(yaml_profile_id) {
+            if let Some((_, comments)) = above_profiles.iter().find(|(known, _)| *known == id) {
+                content.push_str(comments);
+            }
+        }
+        content.push_str(line);
+    }
+    content
+}
+
+/// The profile ID on an `id: <id>` (or `- id: <id>`) line of a YAML file
+fn yaml_profile_id(line: &str) -> Option<&str> {
+    let line = line.strip_prefix("- ").unwrap_or(line).trim_start();
+    let id = line.strip_prefix("id:")?.trim();
+    Some(id.trim_matches(|c| c == '\'' || c == '"'))
+}
+
+/// The format of the profiles file in `config_dir`. Without one (e.g. after it
+/// was quarantined) the format of the last good copy is used, else JSON.
+fn detect_format(config_dir: &Path) -> Result<Format> {
+    let present: Vec<Format> = Format::ALL
+        .into_iter()
+        .filter(|f| config_dir.join(f.profiles_file()).exists())
+        .collect();
+    match present.as_slice() {
+        [] => Ok(Format::ALL
+            .into_iter()
+            .find(|f| config_dir.join(f.last_good_file()).exists())
+            .unwrap_or(Format::Json)),
+        [format] => Ok(*format),
+        _ => Err(StorageError::AmbiguousFormat(
+            present
+                .iter()
+                .map(|f| config_dir.join(f.profiles_file()))
+                .collect(),
+        )),
+    }
+}
+
+/// The default backend: `profiles.json` (or `.toml` / `.yaml`) plus a
+/// `selected-profile` file in the app's config directory
+pub struct FileStore;
+
+#[cfg(feature = "sqlite")]
+impl FileStore {
+    /// Whether `config_dir` holds a profiles file or its last good copy, to
+    /// import into a new database
+    pub fn has_data(config_dir: &Path) -> bool {
+        Format::ALL.into_iter().any(|f| {
+            config_dir.join(f.profiles_file()).exists()
+                || config_dir.join(f.last_good_file()).exists()
+        })
+    }
+}
+
+impl ProfileStore for FileStore {
+    fn load_profiles(&self) -> Result<Vec<Profile>> {
+        lock()?.load_profiles(true)
+    }
+
+    fn read_profiles(&self) -> Result<Vec<Profile>> {
+        lock()?.load_profiles(false)
+    }
+
+    fn update_profiles(
+        &self,
+        change: &mut dyn FnMut(&mut Vec<Profile>) -> Result<()>,
+    ) -> Result<Vec<Profile>> {
+        let lock = lock()?;
+        let mut profiles = lock.load_profiles(true)?;
+        change(&mut profiles)?;
+        lock.save_profiles(&profiles)?;
+        Ok(profiles)
+    }
+
+    fn load_selected_profile(&self) -> Result<Option<String>> {
+        load_selected_profile()
+    }
+
+    fn save_selected_profile(&self, id: &str) -> Result<()> {
+        save_selected_profile(id)
+    }
+
+    fn is_data_file(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| {
+            name == SELECTED_FILE || Format::ALL.iter().any(|f| *name == *f.profiles_file())
+        })
+    }
+
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        lock()?.list_backups()
+    }
+
+    fn restore_backup(&self, backup: &Backup) -> Result<Vec<Profile>> {
+        lock()?.restore_backup(backup)
+    }
+
+    fn has_last_good(&self) -> bool {
+        get_config_dir().is_some_and(|dir| {
+            detect_format(&dir).is_ok_and(|format| dir.join(format.last_good_file()).exists())
+        })
+    }
+
+    fn restore_last_good(&self) -> Result<Vec<Profile>> {
+        lock()?.restore_last_good()
+    }
+
+    fn start_fresh(&self) -> Result<()> {
+        lock()?.start_fresh()
+    }
+
+    fn convert(&self, to: Format) -> Result<()> {
+        lock()?.convert(to)
+    }
+}
+
+/// Exclusive advisory lock on the app's config directory, shared by every
+/// manager process using the same app_id. Released when dropped.
+struct FileLock {
+    _file: Option<File>,
+    config_dir: PathBuf,
+    format: Format,
+}
+
+/// Acquire the storage lock, blocking until other instances release it
+fn lock() -> Result<FileLock> {
+    let config_dir = ensure_config_dir()?;
+    let lock_path = config_dir.join(LOCK_FILE);
+
+    let file = match OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+    {
+        Ok(file) => {
+            file.lock().map_err(|e| StorageError::io(&lock_path, e))?;
+            Some(file)
+        }
+        // Nobody can write to a read-only config dir, so there is nothing to guard
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => None,
+        Err(e) => return Err(StorageError::io(&lock_path, e)),
+    };
+
+    let format = detect_format(&config_dir)?;
+    Ok(FileLock {
+        _file: file,
+        config_dir,
+        format,
+    })
+}
+
+impl FileLock {
+    fn profiles_path(&self) -> PathBuf {
+        self.config_dir.join(self.format.profiles_file())
+    }
+
+    /// Load the profile list. With `repair`, an older file is migrated on disk
+    /// and a damaged one is quarantined; without it nothing on disk changes
+    /// and a damaged file is only reported, as it may still be being written.
+    fn load_profiles(&self, repair: bool) -> Result<Vec<Profile>> {
+        let profiles_path = self.profiles_path();
+        if !profiles_path.exists() {
+            // Until the user restores or starts fresh, a missing file means the
+            // data was lost, not that there are no profiles yet
+            if let Some(quarantined) = self.unrecovered_quarantine()? {
+                return Err(StorageError::Corrupt {
+                    quarantined,
+                    source: "not recovered yet: restore a backup, start fresh from the \
+                             window, or remove the damaged file"
+                        .to_string(),
+                });
+            }
+            return Ok(Vec::new());
+        }
+
+        let content =
+            fs::read_to_string(&profiles_path).map_err(|e| StorageError::io(&profiles_path, e))?;
+
+        match parse_document(self.format, &content) {
+            Ok(parsed) => {
+                if repair && parsed.version < SCHEMA_VERSION {
+                    self.finish_migration(
+                        &profiles_path,
+                        parsed.version,
+                        &content,
+                        &parsed.profiles,
+                    );
+                } else if parsed.added_ids {
+                    // Profiles added by hand must keep the IDs they were given
+                    // here. Best effort, like a migration.
+                    let _ = self.save_profiles(&parsed.profiles);
+                }
+                Ok(parsed.profiles)
+            }
+            Err(ParseFailure::Invalid(source)) if !repair => {
+                Err(StorageError::Parse(profiles_path, source))
+            }
+            Err(ParseFailure::Invalid(source)) => {
+                let quarantined = quarantine(&profiles_path)?;
+                Err(StorageError::Corrupt {
+                    quarantined,
+                    source,
+                })
+            }
+            Err(ParseFailure::TooNew(version)) => Err(StorageError::UnsupportedVersion {
+                path: profiles_path,
+                version,
+                supported: SCHEMA_VERSION,
+            }),
+        }
+    }
+
+    /// The newest quarantined profiles file when it is newer than the last
+    /// good copy, i.e. the user has not restored or started fresh since
+    fn unrecovered_quarantine(&self) -> Result<Option<PathBuf>> {
+        let prefix = format!("{}.corrupt-", self.format.profiles_file());
+        let entries =
+            fs::read_dir(&self.config_dir).map_err(|e| StorageError::io(&self.config_dir, e))?;
+        let newest = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let micros = entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix(&prefix)?
+                    .parse::<u64>()
+                    .ok()?;
+                Some((UNIX_EPOCH + Duration::from_micros(micros), entry.path()))
+            })
+            .max_by_key(|(quarantined_at, _)| *quarantined_at);
+        let Some((quarantined_at, path)) = newest else {
+            return Ok(None);
+        };
+
+        let last_good = self.config_dir.join(self.format.last_good_file());
+        let recovered = fs::metadata(&last_good)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|written| written > quarantined_at);
+        Ok((!recovered).then_some(path))
+    }
+
+    /// Persist a document that was upgraded in memory, keeping the original
+    /// file as `profiles.<extension>.v<old version>`.
+    /// Best effort: a read-only config dir must still be readable, so failures
+    /// to persist the upgrade are ignored and it is retried on the next load.
+    fn finish_migration(
+        &self,
+        profiles_path: &Path,
+        version: u64,
+        original: &str,
+        profiles: &[Profile],
+    ) {
+        let backup_path =
+            profiles_path.with_file_name(format!("{}.v{}", self.format.profiles_file(), version));
+        if !backup_path.exists() && write_atomic(&backup_path, original.as_bytes()).is_err() {
+            return;
+        }
+        if self.save_profiles(profiles).is_err() {
+            return;
+        }
+
+        // Before IDs existed the selection was stored by profile name
+        if let Ok(Some(selected)) = load_selected_profile() {
+            if !profiles.iter().any(|p| p.id == selected) {
+                if let Some(profile) = profiles.iter().find(|p| p.name == selected) {
+                    let _ = save_selected_profile(&profile.id);
+                }
+            }
+        }
+    }
+
+    fn save_profiles(&self, profiles: &[Profile]) -> Result<()> {
+        let profiles_path = self.profiles_path();
+        let previous = check_before_overwrite(self.format, &profiles_path)?;
+        let content = match &previous {
+            Some(previous) => self
+                .format
+                .update(previous, profiles)
+                .map_err(|e| StorageError::Parse(profiles_path.clone(), e))?,
+            None => self.serialize(profiles, "")?,
+        };
+
+        if let Some(previous) = previous {
+            if previous != content {
+                self.rotate_backups(&previous, self.format)?;
+            }
+        }
+        self.write_profiles(&content)
+    }
+
+    /// Serialize `profiles` in the current format, below `header`
+    fn serialize(&self, profiles: &[Profile], header: &str) -> Result<String> {
+        let document = ProfilesDocument {
+            version: SCHEMA_VERSION,
+            profiles: Cow::Borrowed(profiles),
+        };
+        let body = self
+            .format
+            .serialize(&document)
+            .map_err(|e| StorageError::Parse(self.profiles_path(), e))?;
+        Ok(header.to_string() + &body)
+    }
+
+    fn write_profiles(&self, content: &str) -> Result<()> {
+        write_atomic(&self.profiles_path(), content.as_bytes())?;
+
+        // Keep a copy of the last good data to recover from later corruption
+        let backup_path = self.config_dir.join(self.format.last_good_file());
+        write_atomic(&backup_path, content.as_bytes())
+    }
+
+    /// Rewrite the profiles file in another format and remove the old file.
+    /// The old file is kept in the backups folder.
+    fn convert(mut self, to: Format) -> Result<()> {
+        if to == self.format {
+            return Ok(());
+        }
+
+        let profiles = self.load_profiles(true)?;
+        let from = self.format;
+        let old_path = self.profiles_path();
+        let previous = match fs::read_to_string(&old_path) {
+            Ok(previous) => Some(previous),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(StorageError::io(&old_path, e)),
+        };
+
+        self.format = to;
+        let header = previous
+            .as_deref()
+            .map(|previous| from.comment_header(previous))
+            .filter(|_| to != Format::Json)
+            .unwrap_or_default();
+        let content = self.serialize(&profiles, &header)?;
+        if let Some(previous) = &previous {
+            self.rotate_backups(previous, from)?;
+        }
+        self.write_profiles(&content)?;
+
+        for old in [old_path, self.config_dir.join(from.last_good_file())] {
+            match fs::remove_file(&old) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(StorageError::io(&old, e))
+                }
+                _ => {}
+            }
+        }
+        sync_dir(&self.config_dir).map_err(|e| StorageError::io(&self.config_dir, e))
+    }
+
+    /// Replace the profiles file with the last good backup and load it
+    fn restore_last_good(&self) -> Result<Vec<Profile>> {
+        let backup_path = self.config_dir.join(self.format.last_good_file());
+        let profiles_path = self.profiles_path();
+
+        let content = fs::read(&backup_path).map_err(|e| StorageError::io(&backup_path, e))?;
+        write_atomic(&profiles_path, &content)?;
+        // Rewriting the last good copy marks the quarantine as dealt with
+        write_atomic(&backup_path, &content)?;
+        self.load_profiles(true)
+    }
+
+    /// Begin again with an empty profile list after a quarantine. The last
+    /// good copy it replaces is kept in the backups folder.
+    fn start_fresh(&self) -> Result<()> {
+        let backup_path = self.config_dir.join(self.format.last_good_file());
+        match fs::read_to_string(&backup_path) {
+            Ok(last_good) => self.rotate_backups(&last_good, self.format)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(StorageError::io(&backup_path, e)),
+        }
+        self.save_profiles(&[])
+    }
+
+    /// Keep `previous`, written in `format`, in the backups folder and drop
+    /// the oldest backups beyond the retention count
+    fn rotate_backups(&self, previous: &str, format: Format) -> Result<()> {
+        let retention = backup_retention();
+        if retention == 0 {
+            return Ok(());
+        }
+
+        let backups_dir = self.config_dir.join(BACKUPS_DIR);
+        fs::create_dir_all(&backups_dir).map_err(|e| StorageError::io(&backups_dir, e))?;
+
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros())
+            .unwrap_or_default();
+        let backup_path = backups_dir.join(format!("profiles-{}.{}", micros, format.extension()));
+        write_atomic(&backup_path, previous.as_bytes())?;
+
+        for old in self.list_backups()?.into_iter().skip(retention) {
+            fs::remove_file(&old.path).map_err(|e| StorageError::io(&old.path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Previous versions of the profile list, newest first
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        let backups_dir = self.config_dir.join(BACKUPS_DIR);
+        if !backups_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let entries = fs::read_dir(&backups_dir).map_err(|e| StorageError::io(&backups_dir, e))?;
+        let mut backups = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| StorageError::io(&backups_dir, e))?.path();
+            let Some(micros) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("profiles-"))
+                .and_then(|name| name.split_once('.'))
+                .filter(|(_, extension)| Format::from_extension(extension).is_some())
+                .and_then(|(micros, _)| micros.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            // Unreadable backups are not offered for restore
+            let Ok(profiles) = read_document(&path) else {
+                continue;
+            };
+            backups.push(Backup {
+                path,
+                created: UNIX_EPOCH + Duration::from_micros(micros),
+                profile_count: profiles.len(),
+            });
+        }
+
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created));
+        Ok(backups)
+    }
+
+    /// Make a backup the current profile list, in the current format. The
+    /// list being replaced is backed up first, so a restore can itself be
+    /// undone.
+    fn restore_backup(&self, backup: &Backup) -> Result<Vec<Profile>> {
+        let profiles = read_document(&backup.path)?;
+        self.save_profiles(&profiles)?;
+        Ok(profiles)
+    }
+}
+
+/// A profiles file that no longer parses is never overwritten: it is moved
+/// aside first so its contents can still be recovered by hand. A file written
+/// by a newer version is left alone and the save is refused.
+/// Returns the current contents when they are valid.
+fn check_before_overwrite(format: Format, profiles_path: &Path) -> Result<Option<String>> {
+    if !profiles_path.exists() {
+        return Ok(None);
+    }
+
+    let content =
+        fs::read_to_string(profiles_path).map_err(|e| StorageError::io(profiles_path, e))?;
+    match parse_document(format, &content) {
+        Ok(_) => Ok(Some(content)),
+        Err(ParseFailure::Invalid(_)) => quarantine(profiles_path).map(|_| None),
+        Err(ParseFailure::TooNew(version)) => Err(StorageError::UnsupportedVersion {
+            path: profiles_path.to_path_buf(),
+            version,
+            supported: SCHEMA_VERSION,
+        }),
+    }
+}
+
+/// Read a profiles file that is not the live one (e.g. a backup), in the
+/// format given by its extension
+fn read_document(path: &Path) -> Result<Vec<Profile>> {
+    let format = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(Format::from_extension)
+        .unwrap_or(Format::Json);
+    let content = fs::read_to_string(path).map_err(|e| StorageError::io(path, e))?;
+    match parse_document(format, &content) {
+        Ok(parsed) => Ok(parsed.profiles),
+        Err(ParseFailure::Invalid(e)) => Err(StorageError::Parse(path.to_path_buf(), e)),
+        Err(ParseFailure::TooNew(version)) => Err(StorageError::UnsupportedVersion {
+            path: path.to_path_buf(),
+            version,
+            supported: SCHEMA_VERSION,
+        }),
+    }
+}
+
+enum ParseFailure {
+    Invalid(String),
+    TooNew(u64),
+}
+
+/// A profiles file read by `parse_document`
+struct ParsedDocument {
+    /// The version the file was written with
+    version: u64,
+    profiles: Vec<Profile>,
+    /// Whether profiles without an ID (e.g. added by hand) were given one
+    added_ids: bool,
+}
+
+/// Parse a profiles file of any supported schema version, upgrading it in
+/// memory and giving an ID to every profile without one
+fn parse_document(
+    format: Format,
+    content: &str,
+) -> std::result::Result<ParsedDocument, ParseFailure> {
+    let mut value = format.parse(content).map_err(ParseFailure::Invalid)?;
+
+    // Version 1 had no envelope: the file was a bare array of profiles
+    let version = match &value {
+        Value::Array(_) => 1,
+        _ => value.get("version").and_then(Value::as_u64).unwrap_or(0),
+    };
+    if version > SCHEMA_VERSION {
+        return Err(ParseFailure::TooNew(version));
+    }
+
+    if version >= 1 {
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            value = migrate(value);
+        }
+    }
+
+    let added_ids = match value.get_mut("profiles") {
+        Some(Value::Array(items)) => add_missing_ids(items),
+        _ => false,
+    };
+
+    let document: ProfilesDocument =
+        serde_json::from_value(value).map_err(|e| ParseFailure::Invalid(e.to_string()))?;
+    Ok(ParsedDocument {
+        version,
+        profiles: document.profiles.into_owned(),
+        added_ids,
+    })
+}
+
+/// Give a new ID to every profile in `items` without one. Returns whether
+/// any was added.
+fn add_missing_ids(items: &mut [Value]) -> bool {
+    let mut added = false;
+    for item in items {
+        if let Value::Object(fields) = item {
+            if !fields.contains_key("id") {
+                fields.insert("id".to_string(), Value::String(profile::new_id()));
+                added = true;
+            }
+        }
+    }
+    added
+}
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`
+const MIGRATIONS: [fn(Value) -> Value; SCHEMA_VERSION as usize - 1] = [migrate_v1_to_v2];
+
+/// Wrap the bare array in a versioned document and give every profile an ID
+fn migrate_v1_to_v2(mut value: Value) -> Value {
+    if let Value::Array(items) = &mut value {
+        add_missing_ids(items);
+    }
+    json!({ "version": 2, "profiles": value })
+}
+
+/// Move a damaged profiles file aside as `profiles.<extension>.corrupt-<timestamp>`
+/// (in microseconds, to compare it with when the last good copy was written)
+fn quarantine(profiles_path: &Path) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros())
+        .unwrap_or_default();
+    let file_name = profiles_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let quarantined = profiles_path.with_file_name(format!("{}.corrupt-{}", file_name, timestamp));
+    fs::rename(profiles_path, &quarantined).map_err(|e| StorageError::io(profiles_path, e))?;
+    Ok(quarantined)
+}
+
+fn load_selected_profile() -> Result<Option<String>> {
+    let config_dir = get_config_dir().ok_or(StorageError::NoConfigDir)?;
+    let selected_path = config_dir.join(SELECTED_FILE);
+
+    if !selected_path.exists() {
+        return Ok(None);
+    }
+
+    fs::read_to_string(&selected_path)
+        .map(|s| Some(s.trim().to_string()))
+        .map_err(|e| StorageError::io(&selected_path, e))
+}
+
+fn save_selected_profile(id: &str) -> Result<()> {
+    let config_dir = ensure_config_dir()?;
+
+    let selected_path = config_dir.join(SELECTED_FILE);
+    write_atomic(&selected_path, id.as_bytes())
+}
+
+/// Write `content` to a temporary file in the same directory, fsync it and
+/// rename it over `path`, so readers only ever see the old or the new file
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().ok_or(StorageError::NoConfigDir)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = dir.join(format!(".{}.tmp-{}", file_name, std::process::id()));
+
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        sync_dir(dir)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result.map_err(|e| StorageError::io(path, e))
+}
+
+/// Persist the rename itself; directories cannot be opened for syncing on Windows
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ProfileStore, TestConfigDir};
+
+    #[test]
+    fn v1_bare_array_is_migrated_with_new_ids() {
+        let content = r#"[{"name": "Work"}, {"id": "kept", "name": "Home"}]"#;
+        let Ok(ParsedDocument {
+            version, profiles, ..
+        }) = parse_document(Format::Json, content)
+        else {
+            panic!("a version 1 file should parse");
+        };
+        assert_eq!(version, 1);
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "Work");
+        assert!(!profiles[0].id.is_empty());
+        assert_eq!(profiles[1].id, "kept");
+        assert_eq!(profiles[1].name, "Home");
+    }
+
+    #[test]
+    fn current_version_profiles_without_id_get_one() {
+        let content = concat!(
+            r#"{"version": 2, "profiles": "#,
+            r#"[{"id": "a", "name": "Work"}, {"name": "Added by hand"}]}"#,
+        );
+        let Ok(parsed) = parse_document(Format::Json, content) else {
+            panic!("a profile without an ID should not make the file invalid");
+        };
+        assert_eq!(parsed.version, 2);
+        assert!(parsed.added_ids);
+        assert_eq!(parsed.profiles[0].id, "a");
+        assert_eq!(parsed.profiles[1].name, "Added by hand");
+        assert!(!parsed.profiles[1].id.is_empty());
+    }
+
+    #[test]
+    fn ids_added_on_load_are_saved() {
+        let dir = TestConfigDir::new();
+        let path = dir.path.join(Format::Toml.profiles_file());
+        fs::write(
+            &path,
+            concat!(
+                "version = 2\n\n",
+                "# Set up by ops\n[[profiles]]\nname = \"Added by hand\"\nteam = \"ops\"\n",
+            ),
+        )
+        .unwrap();
+
+        let profiles = FileStore.load_profiles().unwrap();
+        assert_eq!(profiles.len(), 1);
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains(&format!("id = \"{}\"", profiles[0].id)));
+        assert!(saved.contains("# Set up by ops"));
+        assert!(saved.contains("team = \"ops\""));
+        assert_eq!(FileStore.load_profiles().unwrap(), profiles);
+        let quarantined = fs::read_dir(&dir.path)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().contains(".corrupt-"));
+        assert!(!quarantined);
+    }
+
+    #[test]
+    fn current_version_is_read_as_is() {
+        let content = r#"{"version": 2, "profiles": [{"id": "a", "name": "Work"}]}"#;
+        let Ok(parsed) = parse_document(Format::Json, content) else {
+            panic!("a version 2 file should parse");
+        };
+        assert_eq!(parsed.version, 2);
+        assert!(!parsed.added_ids);
+        assert_eq!(
+            parsed.profiles,
+            vec![Profile {
+                id: "a".into(),
+                name: "Work".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn toml_and_yaml_documents_parse() {
+        let toml = "version = 2\n\n[[profiles]]\nid = \"a\"\nname = \"Work\"\n";
+        let yaml = "version: 2\nprofiles:\n- id: a\n  name: Work\n";
+        for (format, content) in [(Format::Toml, toml), (Format::Yaml, yaml)] {
+            let Ok(parsed) = parse_document(format, content) else {
+                panic!("{:?} document should parse", format);
+            };
+            assert_eq!(parsed.profiles[0].name, "Work");
+        }
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let content = r#"{"version": 3, "profiles": []}"#;
+        assert!(matches!(
+            parse_document(Format::Json, content),
+            Err(ParseFailure::TooNew(3))
+        ));
+    }
+
+    #[test]
+    fn garbage_is_invalid() {
+        for content in ["not json", r#"{"version": 2, "profiles": [{"id": 1}]}"#] {
+            assert!(matches!(
+                parse_document(Format::Json, content),
+                Err(ParseFailure::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn migrations_cover_every_older_version() {
+        assert_eq!(MIGRATIONS.len() as u64, SCHEMA_VERSION - 1);
+        let migrated = migrate_v1_to_v2(json!([{"name": "Work"}]));
+        assert_eq!(migrated["version"], 2);
+        assert!(migrated["profiles"][0]["id"].is_string());
+    }
+
+    fn profile(id: &str, name: &str) -> Profile {
+        Profile {
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn toml_update_keeps_comments_and_unknown_keys() {
+        let previous = concat!(
+            "# My profiles\nversion = 2\n\n",
+            "# Daily driver\n[[profiles]]\nid = \"a\"\nname = \"Work\"\ncolor = \"red\"\n\n",
+            "[[profiles]]\nid = \"b\"\nname = \"Home\"\n",
+        );
+        let profiles = [profile("a", "Office"), profile("c", "New")];
+        let updated = Format::Toml.update(previous, &profiles).unwrap();
+
+        assert!(updated.contains("# My profiles"));
+        assert!(updated.contains("# Daily driver"));
+        assert!(updated.contains("color = \"red\""));
+        assert!(updated.contains("name = \"Office\""));
+        assert!(!updated.contains("Home"));
+        let Ok(ParsedDocument {
+            profiles: parsed, ..
+        }) = parse_document(Format::Toml, &updated)
+        else {
+            panic!("the updated document should parse:\n{}", updated);
+        };
+        assert_eq!(parsed, profiles);
+    }
+
+    #[test]
+    fn toml_update_to_an_empty_list() {
+        let previous = "version = 2\n\n[[profiles]]\nid = \"a\"\nname = \"Work\"\n";
+        let updated = Format::Toml.update(previous, &[]).unwrap();
+        let Ok(ParsedDocument {
+            profiles: parsed, ..
+        }) = parse_document(Format::Toml, &updated)
+        else {
+            panic!("the updated document should parse:\n{}", updated);
+        };
+        assert!(parsed.is_empty());
+    }
+
+    #[test]
+    fn yaml_update_keeps_unknown_keys() {
+        let previous = concat!(
+            "version: 2\nowner: ops\nprofiles:\n",
+            "- id: a\n  name: Work\n  color: red\n",
+            "- name: Added by hand\n  team: ops\n",
+        );
+        let profiles = [profile("a", "Office"), profile("b", "Added by hand")];
+        let updated = Format::Yaml.update(previous, &profiles).unwrap();
+
+        assert!(updated.contains("owner: ops"));
+        assert!(updated.contains("- id: a\n  name: Office\n  color: red\n"));
+        assert!(updated.contains("- id: b\n  name: Added by hand\n  team: ops\n"));
+        let Ok(ParsedDocument {
+            profiles: parsed, ..
+        }) = parse_document(Format::Yaml, &updated)
+        else {
+            panic!("the updated document should parse:\n{}", updated);
+        };
+        assert_eq!(parsed, profiles);
+    }
+
+    #[test]
+    fn yaml_update_keeps_comments_above_entries() {
+        let previous = concat!(
+            "# My profiles\nversion: 2\nprofiles:\n",
+            "# Daily driver\n- id: a\n  name: Work\n",
+            "# Weekends\n- id: b\n  name: Home\n",
+        );
+        let profiles = [profile("b", "Home"), profile("a", "Office")];
+        let updated = Format::Yaml.update(previous, &profiles).unwrap();
+
+        assert!(updated.starts_with("# My profiles\n"));
+        let daily = updated.find("# Daily driver").expect("entry comment kept");
+        let weekends = updated.find("# Weekends").expect("entry comment kept");
+        assert!(weekends < updated.find("id: b").unwrap());
+        assert!(daily < updated.find("id: a").unwrap());
+        assert!(daily > updated.find("id: b").unwrap());
+        let Ok(ParsedDocument {
+            profiles: parsed, ..
+        }) = parse_document(Format::Yaml, &updated)
+        else {
+            panic!("the updated document should parse:\n{}", updated);
+        };
+        assert_eq!(parsed, profiles);
+    }
+
+    #[test]
+    fn check_before_overwrite_keeps_damaged_and_newer_files() {
+        let dir = TestConfigDir::new();
+        let path = dir.path.join(Format::Json.profiles_file());
+        assert_eq!(check_before_overwrite(Format::Json, &path).unwrap(), None);
+
+        let valid = r#"{"version": 2, "profiles": []}"#;
+        fs::write(&path, valid).unwrap();
+        assert_eq!(
+            check_before_overwrite(Format::Json, &path)
+                .unwrap()
+                .as_deref(),
+            Some(valid)
+        );
+
+        let newer = r#"{"version": 3, "profiles": []}"#;
+        fs::write(&path, newer).unwrap();
+        assert!(matches!(
+            check_before_overwrite(Format::Json, &path),
+            Err(StorageError::UnsupportedVersion { version: 3, .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+
+        fs::write(&path, "{ truncated").unwrap();
+        assert_eq!(check_before_overwrite(Format::Json, &path).unwrap(), None);
+        assert!(!path.exists());
+        let quarantined = lock().unwrap().unrecovered_quarantine().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(quarantined).unwrap(), "{ truncated");
+    }
+
+    #[test]
+    fn quarantine_is_reported_until_recovered() {
+        let dir = TestConfigDir::new();
+        let created = FileStore
+            .update_profiles(&mut |profiles| {
+                profiles.push(profile("a", "Work"));
+                Ok(())
+            })
+            .unwrap();
+        let path = dir.path.join(Format::Json.profiles_file());
+        let last_good = dir.path.join(Format::Json.last_good_file());
+        let good = fs::read_to_string(&last_good).unwrap();
+        assert_eq!(lock().unwrap().unrecovered_quarantine().unwrap(), None);
+
+        fs::write(&path, "{ truncated").unwrap();
+        assert!(matches!(
+            FileStore.load_profiles(),
+            Err(StorageError::Corrupt { .. })
+        ));
+        // The file is gone now, but the damage is still reported and the
+        // last good copy is left alone
+        assert!(!path.exists());
+        assert!(matches!(
+            FileStore.load_profiles(),
+            Err(StorageError::Corrupt { .. })
+        ));
+        let mut unchanged = |_: &mut Vec<Profile>| Ok(());
+        assert!(FileStore.update_profiles(&mut unchanged).is_err());
+        assert_eq!(fs::read_to_string(&last_good).unwrap(), good);
+        assert!(lock().unwrap().unrecovered_quarantine().unwrap().is_some());
+
+        assert_eq!(FileStore.restore_last_good().unwrap(), created);
+        assert_eq!(lock().unwrap().unrecovered_quarantine().unwrap(), None);
+        assert_eq!(FileStore.load_profiles().unwrap(), created);
+    }
+
+    #[test]
+    fn start_fresh_recovers_from_a_quarantine() {
+        let dir = TestConfigDir::new();
+        let path = dir.path.join(Format::Json.profiles_file());
+        fs::write(&path, "{ truncated").unwrap();
+        assert!(FileStore.load_profiles().is_err());
+
+        FileStore.start_fresh().unwrap();
+        assert_eq!(lock().unwrap().unrecovered_quarantine().unwrap(), None);
+        assert_eq!(FileStore.load_profiles().unwrap(), []);
+    }
+}
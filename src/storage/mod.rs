@@ -0,0 +1,819 @@
+mod file;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::profile::Profile;
+
+pub use file::{FileStore, Format};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+const APP_NAME: &str = "simple-profiles-manager";
+const BASE_DIR_ENV: &str = "SIMPLE_PROFILES_MANAGER_DIR";
+const PORTABLE_DIR: &str = "simple-profiles-manager-data";
+const PROFILES_DIR: &str = "profiles";
+const ARCHIVE_DIR: &str = "archive";
+/// Database of the SQLite backend, looked for even without the `sqlite` feature
+const DATABASE_FILE: &str = "profiles.sqlite";
+const DEFAULT_BACKUP_RETENTION: usize = 10;
+
+static APP_ID: OnceLock<String> = OnceLock::new();
+static BASE_DIR: OnceLock<PathBuf> = OnceLock::new();
+static BACKUP_RETENTION: AtomicUsize = AtomicUsize::new(DEFAULT_BACKUP_RETENTION);
+static STORE: OnceLock<Box<dyn ProfileStore>> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The platform config directory could not be determined
+    NoConfigDir,
+    PermissionDenied(PathBuf),
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, String),
+    /// The profiles file could not be parsed and was moved aside to `quarantined`
+    Corrupt {
+        quarantined: PathBuf,
+        source: String,
+    },
+    /// Profiles files in more than one format exist side by side
+    AmbiguousFormat(Vec<PathBuf>),
+    /// A profile ID that cannot be used as the name of its data directory
+    InvalidProfileId(String),
+    /// The profile data is in another backend's storage at the given path
+    WrongBackend {
+        path: PathBuf,
+        backend: &'static str,
+    },
+    /// The stored data was written by a newer version of the manager
+    UnsupportedVersion {
+        path: PathBuf,
+        version: u64,
+        supported: u64,
+    },
+    /// The operation is not available with the selected backend
+    Unsupported(&'static str),
+    /// An update or a copy was abandoned by the caller before it completed
+    Cancelled,
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+impl StorageError {
+    fn io(path: &Path, err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::PermissionDenied {
+            StorageError::PermissionDenied(path.to_path_buf())
+        } else {
+            StorageError::Io(path.to_path_buf(), err)
+        }
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NoConfigDir => write!(f, "could not determine the config directory"),
+            StorageError::PermissionDenied(path) => {
+                write!(f, "permission denied: {}", path.display())
+            }
+            StorageError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            StorageError::Parse(path, err) => {
+                write!(f, "invalid profile data in {}: {}", path.display(), err)
+            }
+            StorageError::Corrupt {
+                quarantined,
+                source,
+            } => write!(
+                f,
+                "profile data is corrupt ({}), the damaged file was moved to {}",
+                source,
+                quarantined.display()
+            ),
+            StorageError::AmbiguousFormat(paths) => write!(
+                f,
+                "found several profiles files ({}); keep only one of them",
+                paths
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            StorageError::InvalidProfileId(id) => write!(
+                f,
+                "profile ID '{}' cannot be used for its data folder (only letters, digits, - and _ are allowed)",
+                id
+            ),
+            StorageError::WrongBackend { path, backend } => write!(
+                f,
+                "the profiles are stored in {}; use --backend {}",
+                path.display(),
+                backend
+            ),
+            StorageError::UnsupportedVersion {
+                path,
+                version,
+                supported,
+            } => write!(
+                f,
+                "{} uses format version {}, but this build only supports up to {}; \
+                 refusing to modify it",
+                path.display(),
+                version,
+                supported
+            ),
+            StorageError::Unsupported(what) => write!(
+                f,
+                "{} is not supported by the selected storage backend",
+                what
+            ),
+            StorageError::Cancelled => write!(f, "cancelled"),
+            #[cfg(feature = "sqlite")]
+            StorageError::Sqlite(err) => write!(f, "database error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Sqlite(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+/// Persistence for one app's profiles and its selected profile
+pub trait ProfileStore: Send + Sync {
+    fn load_profiles(&self) -> Result<Vec<Profile>>;
+
+    /// Load the profile list without changing anything in storage, for
+    /// reloads triggered by changes from other processes
+    fn read_profiles(&self) -> Result<Vec<Profile>> {
+        self.load_profiles()
+    }
+
+    /// Load the profile list, apply `change` once and save the result as a
+    /// single step that other processes cannot interleave with. Nothing is
+    /// saved when `change` fails. Returns the saved list.
+    fn update_profiles(
+        &self,
+        change: &mut dyn FnMut(&mut Vec<Profile>) -> Result<()>,
+    ) -> Result<Vec<Profile>>;
+
+    /// Load the ID of the selected profile
+    fn load_selected_profile(&self) -> Result<Option<String>>;
+
+    /// Store the ID of the selected profile
+    fn save_selected_profile(&self, id: &str) -> Result<()>;
+
+    /// Whether a change to `path` means the stored profile data changed
+    fn is_data_file(&self, path: &Path) -> bool;
+
+    /// Previous versions of the profile list, newest first
+    fn list_backups(&self) -> Result<Vec<Backup>> {
+        Ok(Vec::new())
+    }
+
+    /// Make a backup the current profile list
+    fn restore_backup(&self, _backup: &Backup) -> Result<Vec<Profile>> {
+        Err(StorageError::Unsupported("restoring backups"))
+    }
+
+    /// Whether a copy of the last good profile data is available
+    fn has_last_good(&self) -> bool {
+        false
+    }
+
+    /// Replace the profile data with the last good copy and load it
+    fn restore_last_good(&self) -> Result<Vec<Profile>> {
+        Err(StorageError::Unsupported("restoring the last good copy"))
+    }
+
+    /// Replace damaged profile data with an empty profile list
+    fn start_fresh(&self) -> Result<()> {
+        Err(StorageError::Unsupported("starting fresh"))
+    }
+
+    /// Rewrite the profile data in another file format
+    fn convert(&self, _to: Format) -> Result<()> {
+        Err(StorageError::Unsupported("converting the file format"))
+    }
+}
+
+/// Available storage backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// JSON, TOML or YAML files in the config directory
+    #[value(alias = "json")]
+    File,
+    /// A SQLite database in the config directory
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// A previous version of the profile list
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: SystemTime,
+    pub profile_count: usize,
+}
+
+impl Backup {
+    /// Creation time as `YYYY-MM-DD HH:MM:SS UTC`
+    pub fn created_utc(&self) -> String {
+        let secs = self
+            .created
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let (days, time) = (secs / 86400, secs % 86400);
+
+        // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            year,
+            month,
+            day,
+            time / 3600,
+            time % 3600 / 60,
+            time % 60
+        )
+    }
+}
+
+/// Sanitize the app_id to remove invalid path characters
+/// This ensures the path stays within the simple-profiles-manager directory
+fn sanitize_app_id(app_id: &str) -> String {
+    app_id
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '\0' => '_',
+            '.' if app_id.starts_with('.') => '_', // Prevent hidden directories
+            _ => c,
+        })
+        .collect::<String>()
+        .trim_matches(|c| c == ' ' || c == '.')
+        .to_string()
+}
+
+/// Set the app_id for storage (must be called before any storage operations)
+pub fn set_app_id(app_id: &str) {
+    let sanitized = sanitize_app_id(app_id);
+    APP_ID.set(sanitized).expect("app_id can only be set once");
+}
+
+pub fn get_app_id() -> &'static str {
+    APP_ID
+        .get()
+        .expect("app_id must be set before storage operations")
+}
+
+/// Override the base directory that holds the per-app config directories
+/// (must be called before any storage operations)
+pub fn set_base_dir(dir: PathBuf) {
+    BASE_DIR.set(dir).expect("base dir can only be set once");
+}
+
+/// Base directory used by `--portable`: next to the executable
+pub fn portable_base_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(PORTABLE_DIR))
+}
+
+/// Base directory, in order of precedence: `set_base_dir`, the
+/// `SIMPLE_PROFILES_MANAGER_DIR` environment variable, the platform config dir
+fn get_base_dir() -> Option<PathBuf> {
+    if let Some(dir) = BASE_DIR.get() {
+        return Some(dir.clone());
+    }
+    if let Some(dir) = std::env::var_os(BASE_DIR_ENV).filter(|d| !d.is_empty()) {
+        // Relative to where the manager was started, not to the program's
+        // working directory
+        return std::path::absolute(dir).ok();
+    }
+    dirs::config_dir().map(|p| p.join(APP_NAME))
+}
+
+pub fn get_config_dir() -> Option<PathBuf> {
+    get_base_dir().map(|p| p.join(get_app_id()))
+}
+
+pub fn ensure_config_dir() -> Result<PathBuf> {
+    let config_dir = get_config_dir().ok_or(StorageError::NoConfigDir)?;
+    if !config_dir.exists() {
+        fs::create_dir_all(&config_dir).map_err(|e| StorageError::io(&config_dir, e))?;
+    }
+    Ok(config_dir)
+}
+
+/// Data directory of one profile, `<config dir>/profiles/<id>/`. It is named
+/// after the ID, so it stays the same when the profile is renamed.
+/// `None` when the ID is not a valid folder name (see `profile_dir`).
+pub fn get_profile_dir(id: &str) -> Option<PathBuf> {
+    profile_dir(id).ok()
+}
+
+/// IDs can come from hand-edited files, so they are checked rather than
+/// sanitized: an empty ID would name `profiles/` itself, and sanitizing could
+/// give two profiles the same folder.
+fn profile_dir(id: &str) -> Result<PathBuf> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(StorageError::InvalidProfileId(id.to_string()));
+    }
+    let config_dir = get_config_dir().ok_or(StorageError::NoConfigDir)?;
+    Ok(config_dir.join(PROFILES_DIR).join(id))
+}
+
+pub fn ensure_profile_dir(id: &str) -> Result<PathBuf> {
+    let profile_dir = profile_dir(id)?;
+    if !profile_dir.exists() {
+        fs::create_dir_all(&profile_dir).map_err(|e| StorageError::io(&profile_dir, e))?;
+    }
+    Ok(profile_dir)
+}
+
+/// Move a deleted profile's data directory to `<config dir>/archive/<id>-<timestamp>/`.
+/// Returns where it was moved, `None` when there was no data directory (or the
+/// ID cannot have one).
+pub fn archive_profile_dir(id: &str) -> Result<Option<PathBuf>> {
+    let Some(profile_dir) = get_profile_dir(id).filter(|dir| dir.exists()) else {
+        return Ok(None);
+    };
+    let archive_dir = ensure_config_dir()?.join(ARCHIVE_DIR);
+    fs::create_dir_all(&archive_dir).map_err(|e| StorageError::io(&archive_dir, e))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let archived = archive_dir.join(format!("{}-{}", id, timestamp));
+    fs::rename(&profile_dir, &archived).map_err(|e| StorageError::io(&profile_dir, e))?;
+    Ok(Some(archived))
+}
+
+/// Delete a deleted profile's data directory and everything in it
+pub fn remove_profile_dir(id: &str) -> Result<()> {
+    let Some(profile_dir) = get_profile_dir(id).filter(|dir| dir.exists()) else {
+        return Ok(());
+    };
+    fs::remove_dir_all(&profile_dir).map_err(|e| StorageError::io(&profile_dir, e))
+}
+
+/// How far `copy_profile_dir` has got
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyProgress {
+    pub files_copied: u64,
+    pub files_total: u64,
+    pub bytes_copied: u64,
+    pub bytes_total: u64,
+}
+
+impl CopyProgress {
+    /// Share of the bytes copied so far, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        if self.bytes_total == 0 {
+            if self.files_total == 0 {
+                return 1.0;
+            }
+            return self.files_copied as f32 / self.files_total as f32;
+        }
+        self.bytes_copied as f32 / self.bytes_total as f32
+    }
+}
+
+impl fmt::Display for CopyProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MB: f64 = 1024.0 * 1024.0;
+        write!(
+            f,
+            "{}/{} files, {:.1}/{:.1} MB",
+            self.files_copied,
+            self.files_total,
+            self.bytes_copied as f64 / MB,
+            self.bytes_total as f64 / MB
+        )
+    }
+}
+
+/// Copy the data directory of profile `from` into a new one for profile `to`
+/// and return its path. `progress` is called after each file; `cancel` is
+/// checked between files and stops the copy with `StorageError::Cancelled`.
+/// Symlinks are copied as links, not followed. On any error the partial copy
+/// is removed. When `from` has no data directory, an empty one is created.
+pub fn copy_profile_dir(
+    from: &str,
+    to: &str,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(CopyProgress),
+) -> Result<PathBuf> {
+    let Some(source) = get_profile_dir(from).filter(|dir| dir.exists()) else {
+        return ensure_profile_dir(to);
+    };
+    let target = profile_dir(to)?;
+    if target.exists() {
+        return Err(StorageError::io(
+            &target,
+            io::Error::from(io::ErrorKind::AlreadyExists),
+        ));
+    }
+
+    let mut state = CopyProgress::default();
+    count_files(&source, &mut state)?;
+    progress(state);
+    let result = copy_tree(&source, &target, cancel, &mut state, &mut progress);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&target);
+    }
+    result.map(|()| target)
+}
+
+/// Add the regular files and symlinks under `dir` to the totals of `progress`
+fn count_files(dir: &Path, progress: &mut CopyProgress) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(|e| StorageError::io(dir, e))? {
+        let path = entry.map_err(|e| StorageError::io(dir, e))?.path();
+        let metadata = fs::symlink_metadata(&path).map_err(|e| StorageError::io(&path, e))?;
+        if metadata.is_dir() {
+            count_files(&path, progress)?;
+        } else if metadata.is_file() || metadata.is_symlink() {
+            progress.files_total += 1;
+            if metadata.is_file() {
+                progress.bytes_total += metadata.len();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy_tree(
+    from: &Path,
+    to: &Path,
+    cancel: &AtomicBool,
+    progress: &mut CopyProgress,
+    report: &mut impl FnMut(CopyProgress),
+) -> Result<()> {
+    fs::create_dir(to).map_err(|e| StorageError::io(to, e))?;
+    for entry in fs::read_dir(from).map_err(|e| StorageError::io(from, e))? {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(StorageError::Cancelled);
+        }
+        let entry = entry.map_err(|e| StorageError::io(from, e))?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        let metadata = fs::symlink_metadata(&path).map_err(|e| StorageError::io(&path, e))?;
+
+        if metadata.is_dir() {
+            copy_tree(&path, &target, cancel, progress, report)?;
+            continue;
+        }
+        if metadata.is_symlink() {
+            copy_symlink(&path, &target).map_err(|e| StorageError::io(&target, e))?;
+        } else if metadata.is_file() {
+            fs::copy(&path, &target).map_err(|e| StorageError::io(&path, e))?;
+            progress.bytes_copied += metadata.len();
+        } else {
+            // Sockets, FIFOs and devices belong to a running program, not its data
+            continue;
+        }
+        progress.files_copied += 1;
+        report(*progress);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, target)
+}
+
+#[cfg(windows)]
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+    let link = fs::read_link(source)?;
+    if source.is_dir() {
+        std::os::windows::fs::symlink_dir(link, target)
+    } else {
+        std::os::windows::fs::symlink_file(link, target)
+    }
+}
+
+/// Set how many previous versions of the profile list are kept (0 disables backups)
+pub fn set_backup_retention(count: usize) {
+    BACKUP_RETENTION.store(count, Ordering::Relaxed);
+}
+
+fn backup_retention() -> usize {
+    BACKUP_RETENTION.load(Ordering::Relaxed)
+}
+
+/// Select the storage backend (must be called before any storage operations;
+/// defaults to files)
+pub fn set_backend(backend: Backend) -> Result<()> {
+    let store: Box<dyn ProfileStore> = match backend {
+        Backend::File => {
+            // Once there is a database it holds the profiles: the files show
+            // an empty or stale list (they are left in place by the import)
+            if let Some(config_dir) = get_config_dir() {
+                let database = config_dir.join(DATABASE_FILE);
+                if database.exists() {
+                    return Err(StorageError::WrongBackend {
+                        path: database,
+                        backend: "sqlite",
+                    });
+                }
+            }
+            Box::new(FileStore)
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Box::new(SqliteStore::open()?),
+    };
+    if STORE.set(store).is_err() {
+        panic!("storage backend can only be set once");
+    }
+    Ok(())
+}
+
+fn store() -> &'static dyn ProfileStore {
+    STORE.get_or_init(|| Box::new(FileStore)).as_ref()
+}
+
+pub fn load_profiles() -> Result<Vec<Profile>> {
+    store().load_profiles()
+}
+
+/// Like `load_profiles`, but a damaged file is reported instead of being moved
+/// aside: another process may be in the middle of writing it
+pub fn read_profiles() -> Result<Vec<Profile>> {
+    store().read_profiles()
+}
+
+/// Apply `change` to the current stored profile list and save the result,
+/// so edits made by other instances are kept. Nothing is saved when `change`
+/// fails. Returns the merged list.
+pub fn update_profiles<E: From<StorageError>>(
+    change: impl FnOnce(&mut Vec<Profile>) -> std::result::Result<(), E>,
+) -> std::result::Result<Vec<Profile>, E> {
+    let mut change = Some(change);
+    let mut failure = None;
+    let result = store().update_profiles(&mut |profiles| {
+        let change = change.take().expect("update is only applied once");
+        change(profiles).map_err(|err| {
+            failure = Some(err);
+            StorageError::Cancelled
+        })
+    });
+    match failure {
+        Some(err) => Err(err),
+        None => result.map_err(E::from),
+    }
+}
+
+pub fn load_selected_profile() -> Result<Option<String>> {
+    store().load_selected_profile()
+}
+
+pub fn save_selected_profile(id: &str) -> Result<()> {
+    store().save_selected_profile(id)
+}
+
+/// Whether `path` holds profile data (as opposed to backups, temporary files
+/// or lock files)
+pub fn is_data_file(path: &Path) -> bool {
+    store().is_data_file(path)
+}
+
+pub fn list_backups() -> Result<Vec<Backup>> {
+    store().list_backups()
+}
+
+pub fn restore_backup(backup: &Backup) -> Result<Vec<Profile>> {
+    store().restore_backup(backup)
+}
+
+pub fn has_last_good() -> bool {
+    store().has_last_good()
+}
+
+pub fn restore_last_good() -> Result<Vec<Profile>> {
+    store().restore_last_good()
+}
+
+pub fn start_fresh() -> Result<()> {
+    store().start_fresh()
+}
+
+pub fn convert(to: Format) -> Result<()> {
+    store().convert(to)
+}
+
+/// The app's config directory for tests, emptied for each test using it.
+/// Storage settings are global, so tests using it run one at a time.
+#[cfg(test)]
+pub(crate) struct TestConfigDir {
+    pub path: PathBuf,
+    _serialized: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl TestConfigDir {
+    pub fn new() -> Self {
+        static SERIALIZED: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        static INIT: std::sync::Once = std::sync::Once::new();
+
+        let serialized = SERIALIZED.lock().unwrap_or_else(|e| e.into_inner());
+        INIT.call_once(|| {
+            set_app_id("tests");
+            set_base_dir(std::env::temp_dir().join(format!(
+                "{}-tests-{}",
+                APP_NAME,
+                std::process::id()
+            )));
+        });
+        let path = get_config_dir().expect("the base dir is set");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("the test config dir can be created");
+        Self {
+            path,
+            _serialized: serialized,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestConfigDir {
+    fn drop(&mut self) {
+        if let Some(base_dir) = self.path.parent() {
+            let _ = fs::remove_dir_all(base_dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn backup_at(secs: u64) -> Backup {
+        Backup {
+            path: PathBuf::new(),
+            created: UNIX_EPOCH + Duration::from_secs(secs),
+            profile_count: 0,
+        }
+    }
+
+    #[test]
+    fn profile_dir_accepts_only_plain_ids() {
+        let dir = TestConfigDir::new();
+        for id in ["0c3f9a2e-7d4b-4b8e-9f1a-2b6c8d0e4f5a", "work", "A_b-9"] {
+            assert_eq!(
+                profile_dir(id).unwrap(),
+                dir.path.join(PROFILES_DIR).join(id)
+            );
+        }
+        for id in [
+            "", " ", ".", "..", "../x", "a/b", "a\\b", "/abs", "work.v2", "é",
+        ] {
+            assert!(
+                matches!(profile_dir(id), Err(StorageError::InvalidProfileId(_))),
+                "{:?} was accepted",
+                id
+            );
+            assert_eq!(get_profile_dir(id), None);
+        }
+    }
+
+    #[test]
+    fn invalid_ids_never_touch_the_profiles_folder() {
+        let dir = TestConfigDir::new();
+        let profiles_dir = dir.path.join(PROFILES_DIR);
+        fs::create_dir_all(profiles_dir.join("kept")).unwrap();
+
+        assert!(ensure_profile_dir("..").is_err());
+        assert!(!dir.path.join("..").join(PROFILES_DIR).exists());
+        remove_profile_dir("").unwrap();
+        assert_eq!(archive_profile_dir("").unwrap(), None);
+        assert!(profiles_dir.join("kept").exists());
+    }
+
+    /// A data directory for profile `id` with a nested folder and two files
+    fn fill_profile_dir(id: &str) -> PathBuf {
+        let source = ensure_profile_dir(id).unwrap();
+        fs::create_dir_all(source.join(".config/app")).unwrap();
+        fs::write(source.join("notes.txt"), "hello").unwrap();
+        fs::write(source.join(".config/app/settings"), "x = 1").unwrap();
+        source
+    }
+
+    #[test]
+    fn copy_profile_dir_copies_the_whole_tree() {
+        let _dir = TestConfigDir::new();
+        let source = fill_profile_dir("from");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("notes.txt", source.join("link")).unwrap();
+
+        let mut last = CopyProgress::default();
+        let target = copy_profile_dir("from", "to", &AtomicBool::new(false), |p| last = p).unwrap();
+
+        assert_eq!(target, profile_dir("to").unwrap());
+        assert_eq!(
+            fs::read_to_string(target.join("notes.txt")).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            fs::read_to_string(target.join(".config/app/settings")).unwrap(),
+            "x = 1"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(target.join("link")).unwrap(),
+            Path::new("notes.txt")
+        );
+        assert_eq!(last.files_copied, last.files_total);
+        assert_eq!(last.bytes_copied, 10);
+        assert_eq!(last.bytes_total, 10);
+    }
+
+    #[test]
+    fn copy_profile_dir_without_source_creates_an_empty_dir() {
+        let _dir = TestConfigDir::new();
+        let target = copy_profile_dir("missing", "to", &AtomicBool::new(false), |_| {}).unwrap();
+        assert!(target.is_dir());
+        assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn cancelled_copy_is_removed() {
+        let _dir = TestConfigDir::new();
+        fill_profile_dir("from");
+
+        let cancel = AtomicBool::new(false);
+        let result = copy_profile_dir("from", "to", &cancel, |progress| {
+            // Cancel halfway, once one of the two files is copied
+            if progress.files_copied == 1 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+        assert!(matches!(result, Err(StorageError::Cancelled)));
+        assert!(!profile_dir("to").unwrap().exists());
+    }
+
+    #[test]
+    fn copy_profile_dir_never_overwrites_a_target() {
+        let _dir = TestConfigDir::new();
+        fill_profile_dir("from");
+        let existing = ensure_profile_dir("to").unwrap();
+        fs::write(existing.join("mine"), "keep").unwrap();
+
+        assert!(copy_profile_dir("from", "to", &AtomicBool::new(false), |_| {}).is_err());
+        assert_eq!(fs::read_to_string(existing.join("mine")).unwrap(), "keep");
+        assert!(copy_profile_dir("from", "../to", &AtomicBool::new(false), |_| {}).is_err());
+    }
+
+    #[test]
+    fn created_utc_formats_civil_dates() {
+        assert_eq!(backup_at(0).created_utc(), "1970-01-01 00:00:00 UTC");
+        // Leap day in a year divisible by 400
+        assert_eq!(
+            backup_at(951_827_696).created_utc(),
+            "2000-02-29 12:34:56 UTC"
+        );
+        assert_eq!(
+            backup_at(1_735_689_599).created_utc(),
+            "2024-12-31 23:59:59 UTC"
+        );
+        assert_eq!(
+            backup_at(1_735_689_600).created_utc(),
+            "2025-01-01 00:00:00 UTC"
+        );
+    }
+
+    #[test]
+    fn created_utc_before_the_epoch_is_clamped() {
+        let backup = Backup {
+            created: UNIX_EPOCH - Duration::from_secs(60),
+            ..backup_at(0)
+        };
+        assert_eq!(backup.created_utc(), "1970-01-01 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+
+use super::{ensure_config_dir, FileStore, ProfileStore, Result, StorageError, DATABASE_FILE};
+use crate::profile::Profile;
+
+/// Current version of the database schema, stored in `PRAGMA user_version`
+const SCHEMA_VERSION: u64 = 1;
+
+/// How long to wait for another process holding the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Backend storing profiles in a SQLite database in the app's config directory.
+/// Every write runs in a transaction, so concurrent instances never lose edits.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open the database, creating it on first use. A new database starts
+    /// with the profiles and selection of the file backend, if there are any.
+    pub fn open() -> Result<Self> {
+        let config_dir = ensure_config_dir()?;
+        let path = config_dir.join(DATABASE_FILE);
+        let mut conn = Connection::open(&path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: u64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(StorageError::UnsupportedVersion {
+                path,
+                version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        if version < 1 {
+            tx.execute_batch(
+                "CREATE TABLE profiles (
+                     id TEXT PRIMARY KEY,
+                     name TEXT NOT NULL
+                 );
+                 CREATE TABLE settings (
+                     key TEXT PRIMARY KEY,
+                     value TEXT NOT NULL
+                 );",
+            )?;
+            if FileStore::has_data(&config_dir) {
+                import_file_store(&tx)?;
+            }
+        }
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        tx.commit()?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Copy the file backend's profiles and selection into a new database. The
+/// files are left in place.
+fn import_file_store(tx: &Transaction<'_>) -> Result<()> {
+    let profiles = FileStore.load_profiles()?;
+    replace_profiles(tx, &profiles)?;
+    if let Some(selected) = FileStore.load_selected_profile()? {
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('selected_profile', ?1)",
+            params![selected],
+        )?;
+    }
+    eprintln!(
+        "Imported {} profile(s) from the file backend into the SQLite database",
+        profiles.len()
+    );
+    Ok(())
+}
+
+fn read_profiles(conn: &Connection) -> Result<Vec<Profile>> {
+    let mut stmt = conn.prepare("SELECT id, name FROM profiles")?;
+    let profiles = stmt
+        .query_map([], |row| {
+            Ok(Profile {
+                id: row.get(0)?,
+                name: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(profiles)
+}
+
+fn replace_profiles(tx: &Transaction<'_>, profiles: &[Profile]) -> Result<()> {
+    tx.execute("DELETE FROM profiles", [])?;
+    let mut stmt = tx.prepare("INSERT INTO profiles (id, name) VALUES (?1, ?2)")?;
+    for profile in profiles {
+        stmt.execute(params![profile.id, profile.name])?;
+    }
+    Ok(())
+}
+
+impl ProfileStore for SqliteStore {
+    fn load_profiles(&self) -> Result<Vec<Profile>> {
+        read_profiles(&self.conn())
+    }
+
+    fn update_profiles(
+        &self,
+        change: &mut dyn FnMut(&mut Vec<Profile>) -> Result<()>,
+    ) -> Result<Vec<Profile>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut profiles = read_profiles(&tx)?;
+        change(&mut profiles)?;
+        replace_profiles(&tx, &profiles)?;
+        tx.commit()?;
+        Ok(profiles)
+    }
+
+    fn load_selected_profile(&self) -> Result<Option<String>> {
+        let selected = self
+            .conn()
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'selected_profile'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(selected)
+    }
+
+    fn save_selected_profile(&self, id: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('selected_profile', ?1)",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    fn is_data_file(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name == DATABASE_FILE)
+    }
+}
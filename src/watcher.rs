@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::storage;
+
+/// Watches the per-app config directory for changes made by other processes
+/// (scripts, the CLI, other manager windows)
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    changed: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+    /// Start watching; `on_change` is called from the watcher thread whenever
+    /// the profile data changes, e.g. to wake up the UI
+    pub fn new(on_change: impl Fn() + Send + 'static) -> notify::Result<Self> {
+        let config_dir =
+            storage::ensure_config_dir().map_err(|e| notify::Error::generic(&e.to_string()))?;
+
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&changed);
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            if event.kind.is_access() {
+                return;
+            }
+            if event.paths.iter().any(|p| storage::is_data_file(p)) {
+                flag.store(true, Ordering::SeqCst);
+                on_change();
+            }
+        })?;
+        watcher.watch(&config_dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            changed,
+        })
+    }
+
+    /// Whether the profile data changed since the last call
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}